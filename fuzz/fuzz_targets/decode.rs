@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use spate_bencode::Value;
+use std::sync::LazyLock;
+
+// Built once, creating a runtime per input would dominate the time spent per run.
+static RUNTIME: LazyLock<tokio::runtime::Runtime> = LazyLock::new(|| {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+});
+
+fuzz_target!(|data: &[u8]| {
+    RUNTIME.block_on(async {
+        let mut reader = data;
+        let _ = Value::decode(&mut reader).await;
+    });
+});
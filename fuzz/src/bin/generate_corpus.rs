@@ -0,0 +1,125 @@
+use spate_bencode::Value;
+use std::{collections::BTreeMap, path::PathBuf};
+use tokio::fs;
+
+// Fixed seed so that regenerating the corpus produces byte-identical files.
+const SEED: u64 = 0x5350_4154_4542_4e43;
+const MAX_DEPTH_LIST: usize = 256;
+const LARGE_BYTES_LEN: usize = 1024 * 1024;
+const LARGE_DICT_KEYS: usize = 1000;
+const MIXED_SAMPLES: usize = 32;
+
+// SplitMix64, small enough to keep the generator free of extra dependencies.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next_u64() as u8).collect()
+    }
+
+    fn bytes_below(&mut self, bound: u64) -> Vec<u8> {
+        let len = self.below(bound) as usize;
+        self.bytes(len)
+    }
+}
+
+fn deep_list(depth: usize) -> Value {
+    (0..depth).fold(Value::List(vec![]), |inner, _| Value::List(vec![inner]))
+}
+
+fn large_dict(rng: &mut Rng) -> Value {
+    Value::Dict(
+        (0..LARGE_DICT_KEYS)
+            .map(|i| {
+                let key = Value::Bytes(format!("key{:04}", i).into_bytes());
+                let value = match rng.below(2) {
+                    0 => Value::Integer(rng.next_u64() as i64),
+                    _ => Value::Bytes(rng.bytes_below(32)),
+                };
+                (key, value)
+            })
+            .collect(),
+    )
+}
+
+fn mixed(rng: &mut Rng, depth: usize) -> Value {
+    let kind = if depth == 0 {
+        rng.below(2)
+    } else {
+        rng.below(4)
+    };
+    match kind {
+        0 => Value::Integer(rng.next_u64() as i64 >> rng.below(64)),
+        1 => Value::Bytes(rng.bytes_below(64)),
+        2 => Value::List((0..rng.below(8)).map(|_| mixed(rng, depth - 1)).collect()),
+        _ => Value::Dict(
+            (0..rng.below(8))
+                .map(|_| {
+                    let key = Value::Bytes(rng.bytes_below(16));
+                    (key, mixed(rng, depth - 1))
+                })
+                .collect(),
+        ),
+    }
+}
+
+async fn encode(value: &Value) -> Vec<u8> {
+    let mut buf = Vec::new();
+    value.encode(&mut buf).await.unwrap();
+    buf
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let out = std::env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| root.join("corpus/decode"));
+    fs::create_dir_all(&out).await?;
+
+    let mut rng = Rng(SEED);
+    let mut samples = vec![
+        ("empty_dict", Value::Dict(BTreeMap::new())),
+        ("empty_list", Value::List(vec![])),
+        ("empty_bytes", Value::Bytes(vec![])),
+        ("zero_integer", Value::Integer(0)),
+        ("negative_integer", Value::Integer(-42)),
+        ("min_integer", Value::Integer(i64::MIN)),
+        ("max_integer", Value::Integer(i64::MAX)),
+        ("max_depth_list", deep_list(MAX_DEPTH_LIST)),
+        ("large_bytes", Value::Bytes(rng.bytes(LARGE_BYTES_LEN))),
+        ("large_dict", large_dict(&mut rng)),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value))
+    .collect::<Vec<_>>();
+    samples.extend((0..MIXED_SAMPLES).map(|i| (format!("mixed_{:02}", i), mixed(&mut rng, 4))));
+
+    for (name, value) in samples {
+        fs::write(out.join(&name), encode(&value).await).await?;
+    }
+
+    // Real-world seeds: the torrent files shipped with the workspace.
+    let mut resources = fs::read_dir(root.join("../spate/resources")).await?;
+    while let Some(entry) = resources.next_entry().await? {
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == "torrent") {
+            fs::copy(&path, out.join(entry.file_name())).await?;
+        }
+    }
+
+    Ok(())
+}
@@ -1,9 +1,24 @@
 use anyhow::Error;
 use std::{
     collections::BTreeMap,
+    future::Future,
     io::{self},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddrV4},
+    string::FromUtf8Error,
+    task::{Context, Poll, Waker},
+};
+use tokio::{
+    io::{
+        AsyncBufRead,
+        AsyncBufReadExt,
+        AsyncRead,
+        AsyncReadExt,
+        AsyncWrite,
+        AsyncWriteExt,
+        BufReader,
+    },
+    net::TcpStream,
 };
-use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 const DELIM_TOKEN: u8 = b':';
 const INTEGER_TOKEN: u8 = b'i';
@@ -11,7 +26,9 @@ const LIST_TOKEN: u8 = b'l';
 const DICT_TOKEN: u8 = b'd';
 const END_TOKEN: u8 = b'e';
 
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
+const DEFAULT_BUFFER_CAPACITY: usize = 8 * 1024;
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Value {
     Bytes(Vec<u8>),
     Integer(i64),
@@ -25,7 +42,127 @@ impl Value {
     }
 
     pub async fn encode<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> Result<(), io::Error> {
-        Encoder::new(writer).write_anything(self).await
+        // Only the encoder's own buffer is written out, flushing the writer is up to the caller.
+        let mut encoder = BufferedBencodeEncoder::new(writer, DEFAULT_BUFFER_CAPACITY);
+        encoder.write_anything(self).await?;
+        encoder.flush_buf().await
+    }
+
+    // Same output as encode, for callers that only want the bytes, e.g. to hash them.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut buf);
+            let mut encode = std::pin::pin!(encoder.write_anything(self));
+            // Writing to a Vec never returns Pending, so one poll runs the encoder to the end.
+            match encode
+                .as_mut()
+                .poll(&mut Context::from_waker(Waker::noop()))
+            {
+                Poll::Ready(result) => result.expect("writing to a Vec cannot fail"),
+                Poll::Pending => unreachable!("writing to a Vec never blocks"),
+            }
+        }
+        buf
+    }
+
+    // Anything the BufReader reads past the end of the value is dropped with it, so use
+    // decode_with_buf_reader when more data follows on the same stream.
+    pub async fn decode_tcp(stream: &mut TcpStream) -> Result<Value, DecodeError> {
+        Self::decode(&mut BufReader::new(stream)).await
+    }
+
+    pub async fn encode_tcp(&self, stream: &mut TcpStream) -> Result<(), io::Error> {
+        self.encode(stream).await
+    }
+
+    // Hands the reader back so that subsequent reads see any data it already buffered.
+    pub async fn decode_with_buf_reader<R: AsyncRead + Unpin>(
+        reader: R,
+    ) -> Result<(Value, BufReader<R>), DecodeError> {
+        let mut reader = BufReader::new(reader);
+        let value = Self::decode(&mut reader).await?;
+        Ok((value, reader))
+    }
+
+    // Takes the contents of a Bytes value without copying, returning the value on failure.
+    pub fn into_bytes(self) -> Result<Vec<u8>, Self> {
+        match self {
+            Self::Bytes(b) => Ok(b),
+            value => Err(value),
+        }
+    }
+
+    // Like into_bytes, but also checks that the bytes are valid UTF-8. The bytes are
+    // only copied when that check fails, so the value can be handed back. Non-Bytes
+    // values are reported as an invalid leading byte, as FromUtf8Error has no other form.
+    pub fn into_string(self) -> Result<String, (Self, FromUtf8Error)> {
+        let bytes = self
+            .into_bytes()
+            .map_err(|value| (value, String::from_utf8(vec![0xff]).unwrap_err()))?;
+        String::from_utf8(bytes).map_err(|err| (Self::Bytes(err.as_bytes().to_vec()), err))
+    }
+
+    // False for anything but Bytes, so keys can be compared without matching on them first.
+    pub fn eq_bytes(&self, b: &[u8]) -> bool {
+        matches!(self, Self::Bytes(bytes) if bytes == b)
+    }
+
+    pub fn eq_str(&self, s: &str) -> bool {
+        self.eq_bytes(s.as_bytes())
+    }
+
+    pub fn starts_with_bytes(&self, prefix: &[u8]) -> bool {
+        matches!(self, Self::Bytes(bytes) if bytes.starts_with(prefix))
+    }
+
+    // Approximate heap usage of the tree, as opposed to its encoded length.
+    pub fn recursive_byte_count(&self) -> usize {
+        const POINTER: usize = std::mem::size_of::<usize>();
+        match self {
+            Self::Bytes(b) => b.len(),
+            Self::Integer(_) => std::mem::size_of::<i64>(),
+            Self::List(l) => l.iter().map(|v| POINTER + v.recursive_byte_count()).sum(),
+            Self::Dict(d) => d
+                .iter()
+                .map(|(k, v)| 2 * POINTER + k.recursive_byte_count() + v.recursive_byte_count())
+                .sum(),
+        }
+    }
+
+    // Rebuilds the tree with `f` applied to every Bytes leaf. Dict keys are left as they are.
+    pub fn map_bytes<F: Fn(&[u8]) -> Vec<u8>>(&self, f: F) -> Value {
+        self.map_bytes_keyed(|_, b| f(b))
+    }
+
+    // Like map_bytes, but `f` also gets the keys of the enclosing dicts joined with '.',
+    // e.g. `info.name`. List indices are not part of the path.
+    pub fn map_bytes_keyed<F: Fn(&[u8], &[u8]) -> Vec<u8>>(&self, f: F) -> Value {
+        self.map_leaves(&mut Vec::new(), &f)
+    }
+
+    fn map_leaves<F: Fn(&[u8], &[u8]) -> Vec<u8>>(&self, path: &mut Vec<u8>, f: &F) -> Value {
+        match self {
+            Self::Bytes(b) => Self::Bytes(f(path, b)),
+            Self::Integer(i) => Self::Integer(*i),
+            Self::List(l) => Self::List(l.iter().map(|v| v.map_leaves(path, f)).collect()),
+            Self::Dict(d) => Self::Dict(
+                d.iter()
+                    .map(|(k, v)| {
+                        let len = path.len();
+                        if let Self::Bytes(key) = k {
+                            if len > 0 {
+                                path.push(b'.');
+                            }
+                            path.extend_from_slice(key);
+                        }
+                        let v = v.map_leaves(path, f);
+                        path.truncate(len);
+                        (k.clone(), v)
+                    })
+                    .collect(),
+            ),
+        }
     }
 }
 
@@ -71,10 +208,99 @@ impl From<&str> for Value {
     }
 }
 
+// Later duplicates of a key replace earlier ones, as with BTreeMap::insert.
+impl From<Vec<(String, Value)>> for Value {
+    fn from(value: Vec<(String, Value)>) -> Self {
+        Self::Dict(
+            value
+                .into_iter()
+                .map(|(k, v)| (Self::Bytes(k.into_bytes()), v))
+                .collect(),
+        )
+    }
+}
+
+impl From<Vec<(&str, Value)>> for Value {
+    fn from(value: Vec<(&str, Value)>) -> Self {
+        Self::Dict(value.into_iter().map(|(k, v)| (Self::from(k), v)).collect())
+    }
+}
+
+impl From<Ipv4Addr> for Value {
+    fn from(value: Ipv4Addr) -> Self {
+        Self::Bytes(value.octets().to_vec())
+    }
+}
+
+impl TryFrom<Value> for Ipv4Addr {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Bytes(ref b) => <[u8; 4]>::try_from(b.as_slice())
+                .map(Ipv4Addr::from)
+                .map_err(|_| Error::msg("IPv4 addresses must be exactly 4 bytes")),
+            _ => Err(Error::msg(
+                "Only Byte values can be converted into IPv4 addresses",
+            )),
+        }
+    }
+}
+
+impl From<Ipv6Addr> for Value {
+    fn from(value: Ipv6Addr) -> Self {
+        Self::Bytes(value.octets().to_vec())
+    }
+}
+
+impl TryFrom<Value> for Ipv6Addr {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Bytes(ref b) => <[u8; 16]>::try_from(b.as_slice())
+                .map(Ipv6Addr::from)
+                .map_err(|_| Error::msg("IPv6 addresses must be exactly 16 bytes")),
+            _ => Err(Error::msg(
+                "Only Byte values can be converted into IPv6 addresses",
+            )),
+        }
+    }
+}
+
+// Compact peer format: 4 address octets followed by the big-endian port.
+impl From<SocketAddrV4> for Value {
+    fn from(value: SocketAddrV4) -> Self {
+        let mut bytes = value.ip().octets().to_vec();
+        bytes.extend_from_slice(&value.port().to_be_bytes());
+        Self::Bytes(bytes)
+    }
+}
+
+impl TryFrom<Value> for SocketAddrV4 {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Bytes(ref b) if b.len() == 6 => Ok(SocketAddrV4::new(
+                Ipv4Addr::new(b[0], b[1], b[2], b[3]),
+                u16::from_be_bytes([b[4], b[5]]),
+            )),
+            Value::Bytes(_) => Err(Error::msg(
+                "Compact socket addresses must be exactly 6 bytes",
+            )),
+            _ => Err(Error::msg(
+                "Only Byte values can be converted into socket addresses",
+            )),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum DecodeError {
     IO(std::io::Error),
     DECODER(&'static str),
+    UnknownToken { byte: u8, offset: u64 },
 }
 
 impl std::fmt::Display for DecodeError {
@@ -82,6 +308,13 @@ impl std::fmt::Display for DecodeError {
         match self {
             Self::IO(arg0) => write!(f, "IO Error: {}", arg0),
             Self::DECODER(arg0) => write!(f, "Decoder Error: {}", arg0),
+            Self::UnknownToken { byte, offset } => write!(
+                f,
+                "Unknown bencode token {:#04x} ('{}') at offset {}",
+                byte,
+                (*byte as char).escape_default(),
+                offset
+            ),
         }
     }
 }
@@ -90,39 +323,36 @@ impl std::error::Error for DecodeError {}
 
 pub struct Decoder<'a, R: AsyncBufRead + Unpin> {
     reader: &'a mut R,
+    // Bytes consumed so far, for error messages.
+    offset: u64,
 }
 
 impl<'a, R: AsyncBufRead + Unpin> Decoder<'a, R> {
     pub fn new(reader: &'a mut R) -> Self {
-        Self { reader }
+        Self { reader, offset: 0 }
     }
 
     pub async fn read_anything(&mut self) -> Result<Value, DecodeError> {
-        match self
-            .reader
-            .fill_buf()
-            .await
-            .map_err(DecodeError::IO)?
-            .first()
-        {
-            Some(i) => match i {
-                &INTEGER_TOKEN => self.read_integer().await,
-                &LIST_TOKEN => Box::pin(self.read_list()).await,
-                &DICT_TOKEN => Box::pin(self.read_dict()).await,
-                b'0'..=b'9' => self.read_bytes().await,
-                _ => Err(DecodeError::DECODER("Unknown token")),
-            },
-            None => Err(DecodeError::DECODER("Expected token")),
+        match self.peek().await? {
+            INTEGER_TOKEN => self.read_integer().await,
+            LIST_TOKEN => Box::pin(self.read_list()).await,
+            DICT_TOKEN => Box::pin(self.read_dict()).await,
+            b'0'..=b'9' => self.read_bytes().await,
+            byte => Err(DecodeError::UnknownToken {
+                byte,
+                offset: self.offset,
+            }),
         }
     }
 
     pub async fn read_integer(&mut self) -> Result<Value, DecodeError> {
-        self.reader.consume(1);
+        self.consume(1);
         let mut ret = Vec::new();
-        self.reader
+        self.offset += self
+            .reader
             .read_until(END_TOKEN, &mut ret)
             .await
-            .map_err(DecodeError::IO)?;
+            .map_err(DecodeError::IO)? as u64;
         let int_str = String::from_utf8_lossy(&ret);
         let parsed_int = &int_str[..int_str.len() - 1]
             .parse::<i64>()
@@ -132,10 +362,11 @@ impl<'a, R: AsyncBufRead + Unpin> Decoder<'a, R> {
 
     pub async fn read_bytes(&mut self) -> Result<Value, DecodeError> {
         let mut buf = Vec::new();
-        self.reader
+        self.offset += self
+            .reader
             .read_until(DELIM_TOKEN, &mut buf)
             .await
-            .map_err(DecodeError::IO)?;
+            .map_err(DecodeError::IO)? as u64;
         let length_str = String::from_utf8_lossy(&buf);
         let length = length_str[..length_str.len() - 1]
             .parse::<usize>()
@@ -145,47 +376,67 @@ impl<'a, R: AsyncBufRead + Unpin> Decoder<'a, R> {
             .read_exact(&mut bytes)
             .await
             .map_err(DecodeError::IO)?;
+        self.offset += length as u64;
         Ok(Value::Bytes(bytes))
     }
 
     pub async fn read_list(&mut self) -> Result<Value, DecodeError> {
-        self.reader.consume(1);
+        self.consume(1);
         let mut list = Vec::new();
-        while self
-            .reader
-            .fill_buf()
-            .await
-            .map_err(DecodeError::IO)?
-            .first()
-            != Some(&END_TOKEN)
-        {
+        while self.peek().await? != END_TOKEN {
             list.push(Box::pin(self.read_anything()).await?);
         }
-        self.reader.consume(1);
+        self.consume(1);
         Ok(Value::List(list))
     }
 
     pub async fn read_dict(&mut self) -> Result<Value, DecodeError> {
-        self.reader.consume(1);
         let mut dict = BTreeMap::new();
-        while self
-            .reader
-            .fill_buf()
-            .await
-            .map_err(DecodeError::IO)?
-            .first()
-            != Some(&END_TOKEN)
-        {
+        self.read_dict_streaming(|key, value| {
+            dict.insert(key, value);
+            Ok::<_, DecodeError>(())
+        })
+        .await?;
+        Ok(Value::Dict(dict))
+    }
+
+    // Hands each entry to `callback` as soon as it is decoded instead of building the
+    // whole map, so a caller can keep only the entries it cares about.
+    pub async fn read_dict_streaming<F, E>(&mut self, mut callback: F) -> Result<(), DecodeError>
+    where
+        F: FnMut(Value, Value) -> Result<(), E>,
+        E: Into<DecodeError>,
+    {
+        if self.peek().await? != DICT_TOKEN {
+            return Err(DecodeError::DECODER("Expected dict"));
+        }
+        self.consume(1);
+        while self.peek().await? != END_TOKEN {
             let key = Box::pin(self.read_anything()).await?;
             let value = Box::pin(self.read_anything()).await?;
             if let Value::Bytes(_) = key {
-                dict.insert(key, value);
+                callback(key, value).map_err(Into::into)?;
             } else {
                 return Err(DecodeError::DECODER("Dict key must be a byte string"));
             }
         }
-        self.reader.consume(1);
-        Ok(Value::Dict(dict))
+        self.consume(1);
+        Ok(())
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.reader.consume(amount);
+        self.offset += amount as u64;
+    }
+
+    async fn peek(&mut self) -> Result<u8, DecodeError> {
+        self.reader
+            .fill_buf()
+            .await
+            .map_err(DecodeError::IO)?
+            .first()
+            .copied()
+            .ok_or(DecodeError::DECODER("Expected token"))
     }
 }
 
@@ -207,7 +458,7 @@ impl<'a, W: AsyncWrite + Unpin> Encoder<'a, W> {
         }
     }
 
-    pub async fn write_bytes(&mut self, value: &Vec<u8>) -> Result<(), io::Error> {
+    pub async fn write_bytes(&mut self, value: &[u8]) -> Result<(), io::Error> {
         self.writer
             .write_all(value.len().to_string().as_bytes())
             .await?;
@@ -223,7 +474,7 @@ impl<'a, W: AsyncWrite + Unpin> Encoder<'a, W> {
         Ok(())
     }
 
-    pub async fn write_list(&mut self, value: &Vec<Value>) -> Result<(), io::Error> {
+    pub async fn write_list(&mut self, value: &[Value]) -> Result<(), io::Error> {
         self.writer.write_all(&[LIST_TOKEN]).await?;
         for v in value.iter() {
             self.write_anything(v).await?;
@@ -243,12 +494,146 @@ impl<'a, W: AsyncWrite + Unpin> Encoder<'a, W> {
     }
 }
 
+// Accumulates encoded output and hands it to the inner writer in chunks of
+// roughly `capacity` bytes instead of one `write_all` per token.
+pub struct BufferedBencodeEncoder<W: AsyncWrite + Unpin> {
+    inner: W,
+    buf: Vec<u8>,
+    capacity: usize,
+}
+
+impl<W: AsyncWrite + Unpin> BufferedBencodeEncoder<W> {
+    pub fn new(inner: W, capacity: usize) -> Self {
+        Self {
+            inner,
+            buf: Vec::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub async fn write_anything(&mut self, value: &Value) -> Result<(), io::Error> {
+        match value {
+            Value::Bytes(ref b) => self.write_bytes(b).await,
+            Value::Integer(ref i) => self.write_integer(i).await,
+            Value::List(ref l) => Box::pin(self.write_list(l)).await,
+            Value::Dict(ref d) => Box::pin(self.write_dict(d)).await,
+        }
+    }
+
+    pub async fn write_bytes(&mut self, value: &[u8]) -> Result<(), io::Error> {
+        self.put(value.len().to_string().as_bytes()).await?;
+        self.put(&[DELIM_TOKEN]).await?;
+        self.put(value).await
+    }
+
+    pub async fn write_integer(&mut self, value: &i64) -> Result<(), io::Error> {
+        self.put(&[INTEGER_TOKEN]).await?;
+        self.put(value.to_string().as_bytes()).await?;
+        self.put(&[END_TOKEN]).await
+    }
+
+    pub async fn write_list(&mut self, value: &[Value]) -> Result<(), io::Error> {
+        self.put(&[LIST_TOKEN]).await?;
+        for v in value.iter() {
+            self.write_anything(v).await?;
+        }
+        self.put(&[END_TOKEN]).await
+    }
+
+    pub async fn write_dict(&mut self, value: &BTreeMap<Value, Value>) -> Result<(), io::Error> {
+        self.put(&[DICT_TOKEN]).await?;
+        for (k, v) in value {
+            self.write_anything(k).await?;
+            self.write_anything(v).await?;
+        }
+        self.put(&[END_TOKEN]).await
+    }
+
+    pub async fn flush(&mut self) -> Result<(), io::Error> {
+        self.flush_buf().await?;
+        self.inner.flush().await
+    }
+
+    async fn flush_buf(&mut self) -> Result<(), io::Error> {
+        if !self.buf.is_empty() {
+            self.inner.write_all(&self.buf).await?;
+            self.buf.clear();
+        }
+        Ok(())
+    }
+
+    async fn put(&mut self, bytes: &[u8]) -> Result<(), io::Error> {
+        if self.buf.len() + bytes.len() > self.capacity {
+            self.flush_buf().await?;
+        }
+        // Anything that would not fit in an empty buffer goes straight through.
+        if bytes.len() > self.capacity {
+            return self.inner.write_all(bytes).await;
+        }
+        self.buf.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Cursor;
+    use std::{
+        io::Cursor,
+        pin::Pin,
+        task::{Context, Poll},
+    };
     use tokio::io::{BufReader, BufWriter};
 
+    #[derive(Default)]
+    struct CountingWriter {
+        writes: usize,
+        largest_write: usize,
+        flushes: usize,
+        data: Vec<u8>,
+    }
+
+    impl AsyncWrite for CountingWriter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<Result<usize, io::Error>> {
+            self.writes += 1;
+            self.largest_write = self.largest_write.max(buf.len());
+            self.data.extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), io::Error>> {
+            self.flushes += 1;
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<(), io::Error>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn large_dict() -> Value {
+        Value::Dict(
+            (0..1000)
+                .map(|i| {
+                    (
+                        Value::Bytes(format!("key{:04}", i).into_bytes()),
+                        Value::Integer(i),
+                    )
+                })
+                .collect(),
+        )
+    }
+
     #[tokio::test]
     async fn encode_bytes() {
         let input = Value::Bytes("hello world".into());
@@ -257,7 +642,7 @@ mod tests {
         let got = input.encode(&mut writer).await;
         assert!(got.is_ok());
 
-        writer.flush().await;
+        writer.flush().await.unwrap();
         assert_eq!(buf, b"11:hello world");
     }
 
@@ -269,7 +654,7 @@ mod tests {
         let got = input.encode(&mut writer).await;
         assert!(got.is_ok());
 
-        writer.flush().await;
+        writer.flush().await.unwrap();
         assert_eq!(buf, b"i1234e");
     }
 
@@ -284,7 +669,7 @@ mod tests {
         let got = input.encode(&mut writer).await;
         assert!(got.is_ok());
 
-        writer.flush().await;
+        writer.flush().await.unwrap();
         assert_eq!(buf, b"l11:hello worldi1234ee");
     }
 
@@ -299,7 +684,7 @@ mod tests {
         let got = input.encode(&mut writer).await;
         assert!(got.is_ok());
 
-        writer.flush().await;
+        writer.flush().await.unwrap();
         assert_eq!(buf, b"d4:key16:value14:key2i1234ee");
     }
 
@@ -347,4 +732,424 @@ mod tests {
             ]))
         );
     }
+
+    #[tokio::test]
+    async fn buffered_encoder_matches_encoder() {
+        let input = large_dict();
+        let mut unbuffered = CountingWriter::default();
+        Encoder::new(&mut unbuffered)
+            .write_anything(&input)
+            .await
+            .unwrap();
+
+        let mut buffered = CountingWriter::default();
+        let mut encoder = BufferedBencodeEncoder::new(&mut buffered, 1024);
+        encoder.write_anything(&input).await.unwrap();
+        encoder.flush().await.unwrap();
+
+        assert_eq!(buffered.data, unbuffered.data);
+        assert_eq!(unbuffered.writes, 2 + 1000 * 6);
+        // None of the values is larger than the buffer, so each write carries at most one
+        // buffer's worth and this many writes is the least it can take.
+        assert!(buffered.writes >= unbuffered.data.len().div_ceil(1024));
+        assert!(buffered.writes < unbuffered.writes);
+        assert!(buffered.largest_write <= 1024);
+    }
+
+    #[tokio::test]
+    async fn buffered_encoder_passes_large_values_through() {
+        let input = Value::List(vec![
+            Value::Integer(1),
+            Value::Bytes(vec![b'x'; 4096]),
+            Value::Integer(2),
+        ]);
+        let mut writer = CountingWriter::default();
+        let mut encoder = BufferedBencodeEncoder::new(&mut writer, 1024);
+        encoder.write_anything(&input).await.unwrap();
+        encoder.flush().await.unwrap();
+
+        assert_eq!(writer.data, input.to_bytes());
+        // The buffered prefix, the bytes value in one direct write, then the rest.
+        assert_eq!(writer.writes, 3);
+        assert_eq!(writer.largest_write, 4096);
+    }
+
+    #[tokio::test]
+    async fn encode_does_not_flush_writer() {
+        let input = large_dict();
+        let mut writer = CountingWriter::default();
+        input.encode(&mut writer).await.unwrap();
+        assert_eq!(writer.data, input.to_bytes());
+        assert_eq!(writer.flushes, 0);
+    }
+
+    #[tokio::test]
+    async fn buffered_encoder_writes_large_bytes_directly() {
+        let input = Value::Bytes(vec![b'x'; 4096]);
+        let mut writer = CountingWriter::default();
+        let mut encoder = BufferedBencodeEncoder::new(&mut writer, 1024);
+        encoder.write_anything(&input).await.unwrap();
+        encoder.flush().await.unwrap();
+
+        assert_eq!(writer.writes, 2);
+        assert_eq!(&writer.data[..5], b"4096:");
+        assert_eq!(writer.data.len(), 4101);
+    }
+
+    #[test]
+    fn ipv4_roundtrip() {
+        let value = Value::from(Ipv4Addr::LOCALHOST);
+        assert_eq!(value, Value::Bytes(vec![127, 0, 0, 1]));
+        assert_eq!(Ipv4Addr::try_from(value).unwrap(), Ipv4Addr::LOCALHOST);
+        assert!(Ipv4Addr::try_from(Value::Bytes(vec![127, 0, 0])).is_err());
+        assert!(Ipv4Addr::try_from(Value::Integer(1)).is_err());
+    }
+
+    #[test]
+    fn ipv6_roundtrip() {
+        let addr = "2001:db8::1".parse::<Ipv6Addr>().unwrap();
+        let value = Value::from(addr);
+        assert_eq!(
+            value,
+            Value::Bytes(vec![
+                0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1
+            ])
+        );
+        assert_eq!(Ipv6Addr::try_from(value).unwrap(), addr);
+        assert!(Ipv6Addr::try_from(Value::Bytes(vec![0; 4])).is_err());
+    }
+
+    #[test]
+    fn socket_addr_v4_roundtrip() {
+        let addr = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 6881);
+        let value = Value::from(addr);
+        assert_eq!(value, Value::Bytes(vec![10, 0, 0, 1, 0x1a, 0xe1]));
+        assert_eq!(SocketAddrV4::try_from(value).unwrap(), addr);
+        assert!(SocketAddrV4::try_from(Value::Bytes(vec![10, 0, 0, 1])).is_err());
+    }
+
+    #[test]
+    fn recursive_byte_count() {
+        const POINTER: usize = std::mem::size_of::<usize>();
+        let input = Value::Dict(BTreeMap::from([
+            (Value::from("data"), Value::Bytes(vec![0; 1000])),
+            (
+                Value::from("list"),
+                Value::List(vec![Value::Integer(1), Value::from("ab")]),
+            ),
+        ]));
+        let got = input.recursive_byte_count();
+        assert!(got >= 1000);
+        assert_eq!(
+            got,
+            (2 * POINTER + 4 + 1000) + (2 * POINTER + 4 + 2 * POINTER + 8 + 2)
+        );
+        assert_eq!(Value::Bytes(vec![]).recursive_byte_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn into_string_moves_bytes() {
+        let value = Value::decode(&mut &b"11:hello world"[..]).await.unwrap();
+        let ptr = match &value {
+            Value::Bytes(b) => b.as_ptr(),
+            _ => panic!("expected bytes"),
+        };
+        let got = value.into_string().unwrap();
+        assert_eq!(got, "hello world");
+        assert_eq!(got.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn into_string_invalid() {
+        let (value, err) = Value::Bytes(vec![b'a', 0xff]).into_string().unwrap_err();
+        assert_eq!(value, Value::Bytes(vec![b'a', 0xff]));
+        assert_eq!(err.utf8_error().valid_up_to(), 1);
+
+        let (value, _) = Value::Integer(1).into_string().unwrap_err();
+        assert_eq!(value, Value::Integer(1));
+    }
+
+    #[test]
+    fn into_bytes() {
+        let bytes = vec![0, 1, 2];
+        let ptr = bytes.as_ptr();
+        let got = Value::Bytes(bytes).into_bytes().unwrap();
+        assert_eq!(got.as_ptr(), ptr);
+        assert_eq!(
+            Value::List(vec![]).into_bytes().unwrap_err(),
+            Value::List(vec![])
+        );
+    }
+
+    #[tokio::test]
+    async fn tcp_loopback() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let value = Value::decode_tcp(&mut stream).await.unwrap();
+            value.encode_tcp(&mut stream).await.unwrap();
+            value
+        });
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let input = Value::List(vec![Value::from("ping"), Value::Integer(1)]);
+        input.encode_tcp(&mut client).await.unwrap();
+        assert_eq!(server.await.unwrap(), input);
+        assert_eq!(Value::decode_tcp(&mut client).await.unwrap(), input);
+    }
+
+    #[tokio::test]
+    async fn decode_with_buf_reader_keeps_buffered_data() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream.write_all(b"4:spami42etrailer").await.unwrap();
+        });
+
+        let client = TcpStream::connect(addr).await.unwrap();
+        server.await.unwrap();
+        let (first, reader) = Value::decode_with_buf_reader(client).await.unwrap();
+        assert_eq!(first, Value::from("spam"));
+        let (second, mut reader) = Value::decode_with_buf_reader(reader).await.unwrap();
+        assert_eq!(second, Value::Integer(42));
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).await.unwrap();
+        assert_eq!(rest, "trailer");
+    }
+
+    #[test]
+    fn map_bytes_uppercase() {
+        let input = Value::Dict(BTreeMap::from([
+            (Value::from("name"), Value::from("ubuntu")),
+            (Value::from("length"), Value::Integer(42)),
+            (
+                Value::from("files"),
+                Value::List(vec![Value::from("a.iso"), Value::List(vec![])]),
+            ),
+        ]));
+        let got = input.map_bytes(|b| b.to_ascii_uppercase());
+        let expected = Value::Dict(BTreeMap::from([
+            (Value::from("name"), Value::from("UBUNTU")),
+            (Value::from("length"), Value::Integer(42)),
+            (
+                Value::from("files"),
+                Value::List(vec![Value::from("A.ISO"), Value::List(vec![])]),
+            ),
+        ]));
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn map_bytes_keyed_paths() {
+        let input = Value::Dict(BTreeMap::from([
+            (Value::from("announce"), Value::from("url")),
+            (
+                Value::from("info"),
+                Value::Dict(BTreeMap::from([
+                    (Value::from("name"), Value::from("n")),
+                    (Value::from("path"), Value::List(vec![Value::from("p")])),
+                ])),
+            ),
+        ]));
+        let got = input.map_bytes_keyed(|path, b| [path, b":", b].concat());
+        let expected = Value::Dict(BTreeMap::from([
+            (Value::from("announce"), Value::from("announce:url")),
+            (
+                Value::from("info"),
+                Value::Dict(BTreeMap::from([
+                    (Value::from("name"), Value::from("info.name:n")),
+                    (
+                        Value::from("path"),
+                        Value::List(vec![Value::from("info.path:p")]),
+                    ),
+                ])),
+            ),
+        ]));
+        assert_eq!(got, expected);
+        assert_eq!(
+            Value::from("top").map_bytes_keyed(|path, b| [path, b].concat()),
+            Value::from("top")
+        );
+    }
+
+    #[tokio::test]
+    async fn read_dict_streaming_keeps_selected_entries() {
+        let data = b"d6:lengthi10e4:name4:file12:piece lengthi16384e6:pieces4:abcde";
+        let mut reader = &data[..];
+        let mut calls = 0;
+        let mut pieces = Vec::new();
+        Decoder::new(&mut reader)
+            .read_dict_streaming(|key, value| {
+                if key.eq_str("pieces") {
+                    calls += 1;
+                    pieces.push(value);
+                }
+                Ok::<_, DecodeError>(())
+            })
+            .await
+            .unwrap();
+        assert_eq!(calls, 1);
+        assert_eq!(pieces, vec![Value::from("abcd")]);
+        assert!(reader.is_empty());
+    }
+
+    #[tokio::test]
+    async fn read_dict_streaming_large_dict() {
+        let mut buf = Vec::new();
+        large_dict().encode(&mut buf).await.unwrap();
+        let mut entries = 0;
+        let mut kept = 0;
+        Decoder::new(&mut buf.as_slice())
+            .read_dict_streaming(|_, value| {
+                // Every entry is dropped here, so at most one is alive at a time.
+                entries += 1;
+                kept = kept.max(value.recursive_byte_count());
+                Ok::<_, DecodeError>(())
+            })
+            .await
+            .unwrap();
+        let Value::Dict(dict) = large_dict() else {
+            unreachable!()
+        };
+        assert_eq!(entries, dict.len());
+        assert!(kept * 100 < large_dict().recursive_byte_count());
+    }
+
+    #[tokio::test]
+    async fn read_dict_streaming_errors() {
+        let mut decoder_input = &b"li1ee"[..];
+        assert!(Decoder::new(&mut decoder_input)
+            .read_dict_streaming(|_, _| Ok::<_, DecodeError>(()))
+            .await
+            .is_err());
+
+        let err = Decoder::new(&mut &b"d1:ai1ee"[..])
+            .read_dict_streaming(|_, _| Err(DecodeError::DECODER("stop")))
+            .await;
+        assert!(matches!(err, Err(DecodeError::DECODER("stop"))));
+    }
+
+    #[test]
+    fn eq_bytes_and_str() {
+        let value = Value::from("announce");
+        assert!(value.eq_bytes(b"announce"));
+        assert!(value.eq_str("announce"));
+        assert!(!value.eq_str("announce-list"));
+        assert!(!value.eq_bytes(b""));
+
+        let empty = Value::Bytes(vec![]);
+        assert!(empty.eq_bytes(b""));
+        assert!(empty.eq_str(""));
+
+        let non_ascii = Value::from("трекер");
+        assert!(non_ascii.eq_bytes("трекер".as_bytes()));
+        assert!(!Value::Bytes(vec![0xff, 0xfe]).eq_str("\u{fffd}\u{fffd}"));
+
+        assert!(!Value::Integer(1).eq_bytes(b"1"));
+        assert!(!Value::Integer(0).eq_str(""));
+    }
+
+    #[test]
+    fn starts_with_bytes() {
+        let value = Value::from("announce-list");
+        assert!(value.starts_with_bytes(b"announce"));
+        assert!(value.starts_with_bytes(b""));
+        assert!(!value.starts_with_bytes(b"announce-list-extra"));
+        assert!(Value::from("трекер").starts_with_bytes(&"трекер".as_bytes()[..2]));
+        assert!(Value::Bytes(vec![]).starts_with_bytes(b""));
+        assert!(!Value::Bytes(vec![]).starts_with_bytes(b"a"));
+        assert!(!Value::Integer(1).starts_with_bytes(b""));
+    }
+
+    #[tokio::test]
+    async fn to_bytes_matches_encode() {
+        let input = Value::Dict(BTreeMap::from([
+            (Value::from("list"), Value::List(vec![Value::Integer(-1)])),
+            (Value::from("dict"), large_dict()),
+            (Value::from("bytes"), Value::Bytes(vec![0xff, 0])),
+        ]));
+        let mut buf = Vec::new();
+        input.encode(&mut buf).await.unwrap();
+        assert_eq!(input.to_bytes(), buf);
+    }
+
+    #[tokio::test]
+    async fn unknown_token_first_byte() {
+        let err = Value::decode(&mut &b"q"[..]).await.unwrap_err();
+        assert!(matches!(
+            err,
+            DecodeError::UnknownToken {
+                byte: b'q',
+                offset: 0
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Unknown bencode token 0x71 ('q') at offset 0"
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_token_offset() {
+        // `d` and `l`, then 5 + 8 + 6 bytes of list items before the `q`
+        let data = b"dl3:abci-12345e4:spamqee";
+        let err = Value::decode(&mut &data[..]).await.unwrap_err();
+        assert!(matches!(
+            err,
+            DecodeError::UnknownToken {
+                byte: b'q',
+                offset: 21
+            }
+        ));
+        let err = Value::decode(&mut &b"l\x00e"[..]).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown bencode token 0x00 ('\\u{0}') at offset 1"
+        );
+    }
+
+    #[test]
+    fn dict_from_pairs() {
+        let expected = Value::Dict(BTreeMap::from([
+            (Value::from("interval"), Value::Integer(1800)),
+            (Value::from("complete"), Value::Integer(5)),
+            (Value::from("incomplete"), Value::Integer(2)),
+            (Value::from("peers"), Value::Bytes(vec![])),
+            (Value::from("tracker id"), Value::from("abc")),
+        ]));
+        let borrowed = Value::from(vec![
+            ("tracker id", Value::from("abc")),
+            ("peers", Value::Bytes(vec![])),
+            ("interval", Value::Integer(1800)),
+            ("incomplete", Value::Integer(2)),
+            ("complete", Value::Integer(5)),
+        ]);
+        assert_eq!(borrowed, expected);
+        let owned = Value::from(vec![
+            (String::from("complete"), Value::Integer(5)),
+            (String::from("incomplete"), Value::Integer(2)),
+            (String::from("interval"), Value::Integer(1800)),
+            (String::from("peers"), Value::Bytes(vec![])),
+            (String::from("tracker id"), Value::from("abc")),
+        ]);
+        assert_eq!(owned, expected);
+    }
+
+    #[test]
+    fn dict_from_pairs_duplicate_keys() {
+        let value = Value::from(vec![
+            ("a", Value::Integer(1)),
+            ("b", Value::Integer(2)),
+            ("a", Value::Integer(3)),
+        ]);
+        assert_eq!(
+            value,
+            Value::Dict(BTreeMap::from([
+                (Value::from("a"), Value::Integer(3)),
+                (Value::from("b"), Value::Integer(2)),
+            ]))
+        );
+    }
 }
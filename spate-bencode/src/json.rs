@@ -0,0 +1,96 @@
+use crate::Value;
+use serde_json::Value as JsonValue;
+
+#[derive(Debug)]
+pub enum ConversionError {
+    Json(serde_json::Error),
+    UnsupportedJsonType(&'static str),
+}
+
+impl std::fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Json(arg0) => write!(f, "JSON Error: {}", arg0),
+            Self::UnsupportedJsonType(arg0) => {
+                write!(f, "JSON {} values cannot be represented in bencode", arg0)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+impl Value {
+    pub fn from_json_str(s: &str) -> Result<Value, ConversionError> {
+        let json: JsonValue = serde_json::from_str(s).map_err(ConversionError::Json)?;
+        Self::try_from(json)
+    }
+}
+
+impl TryFrom<JsonValue> for Value {
+    type Error = ConversionError;
+
+    fn try_from(value: JsonValue) -> Result<Self, Self::Error> {
+        match value {
+            JsonValue::String(s) => Ok(Value::Bytes(s.into_bytes())),
+            JsonValue::Number(n) => n
+                .as_i64()
+                .map(Value::Integer)
+                .ok_or(ConversionError::UnsupportedJsonType("non-integer number")),
+            JsonValue::Array(a) => a
+                .into_iter()
+                .map(Value::try_from)
+                .collect::<Result<_, _>>()
+                .map(Value::List),
+            JsonValue::Object(o) => o
+                .into_iter()
+                .map(|(k, v)| Ok((Value::Bytes(k.into_bytes()), Value::try_from(v)?)))
+                .collect::<Result<_, _>>()
+                .map(Value::Dict),
+            JsonValue::Bool(_) => Err(ConversionError::UnsupportedJsonType("boolean")),
+            JsonValue::Null => Err(ConversionError::UnsupportedJsonType("null")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn from_json_str_nested() {
+        let input = r#"{
+            "peers": [{"ip": "10.0.0.1", "port": 6881}, {"ip": "::1", "port": -1}],
+            "interval": 1800,
+            "failure": {"codes": [[], [1, 2]], "reason": ""}
+        }"#;
+        let value = Value::from_json_str(input).unwrap();
+        let mut buf = Vec::new();
+        value.encode(&mut buf).await.unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "d7:failured5:codeslleli1ei2eee6:reason0:e8:intervali1800e\
+             5:peersld2:ip8:10.0.0.14:porti6881eed2:ip3:::14:porti-1eeee"
+        );
+    }
+
+    #[test]
+    fn from_json_str_unsupported() {
+        for input in [
+            "1.5",
+            "true",
+            "null",
+            "[1, false]",
+            r#"{"a": 18446744073709551615}"#,
+        ] {
+            assert!(matches!(
+                Value::from_json_str(input),
+                Err(ConversionError::UnsupportedJsonType(_))
+            ));
+        }
+        assert!(matches!(
+            Value::from_json_str("{"),
+            Err(ConversionError::Json(_))
+        ));
+    }
+}
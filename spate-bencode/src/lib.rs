@@ -1,6 +1,13 @@
 mod bencode;
 mod deserializer;
+#[cfg(feature = "serde-json")]
+mod json;
+#[cfg(feature = "serializer")]
 mod serializer;
 
 pub use bencode::*;
 pub use deserializer::*;
+#[cfg(feature = "serde-json")]
+pub use json::*;
+#[cfg(feature = "serializer")]
+pub use serializer::*;
@@ -1,17 +1,501 @@
-// use crate::bencode::Value;
-// use serde::{ser, Serialize};
-// use std::io::{self, Error};
-
-// pub struct Serializer<W: io::Write> {}
-
-// pub fn write<T, W: io::Write>(value: &T, writer: W) -> Result<(), Error>
-// where
-//     T: Serialize,
-// {
-//     let mut serializer = Serializer { writer };
-//     value.serialize(&mut serializer)
-// }
-
-// impl<'a> ser::Serializer for &'a mut Serializer {
-//     type Ok = Value;
-// }
+use crate::bencode::Value;
+use serde::{ser, Serialize};
+use std::collections::BTreeMap;
+use tokio::io::AsyncWrite;
+
+#[derive(Debug)]
+pub enum BencodeSerializerError {
+    IoError(tokio::io::Error),
+    EncodeError(String),
+}
+
+impl std::fmt::Display for BencodeSerializerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            Self::IoError(err) => err.fmt(f),
+            Self::EncodeError(ref msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for BencodeSerializerError {}
+
+impl ser::Error for BencodeSerializerError {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: std::fmt::Display,
+    {
+        Self::EncodeError(msg.to_string())
+    }
+}
+
+fn unsupported(what: &str) -> BencodeSerializerError {
+    BencodeSerializerError::EncodeError(format!("{} cannot be represented in bencode", what))
+}
+
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, BencodeSerializerError> {
+    value
+        .serialize(BencodeSerializer)?
+        .ok_or_else(|| unsupported("A value without content"))
+}
+
+// Not incremental: the whole value is serialized in memory before anything is written.
+// serde serializers are synchronous and dict keys have to be sorted before the first
+// entry can be written, so the Value tree is built first and then encoded.
+pub async fn to_writer<T: Serialize + ?Sized, W: AsyncWrite + Unpin>(
+    value: &T,
+    writer: &mut W,
+) -> Result<(), BencodeSerializerError> {
+    to_value(value)?
+        .encode(writer)
+        .await
+        .map_err(BencodeSerializerError::IoError)
+}
+
+// `None` and unit values produce no output, which is how `Option` fields get skipped.
+// Inside a sequence they are an error, as dropping them would change its length.
+//
+// serde hands a plain `Vec<u8>` or `[u8; N]` over as a sequence of integers, which is
+// encoded as a list. Fields meant to be byte strings, such as `pieces`, need
+// `#[serde(with = "serde_bytes")]` to become Bytes.
+pub struct BencodeSerializer;
+
+impl ser::Serializer for BencodeSerializer {
+    type Ok = Option<Value>;
+    type Error = BencodeSerializerError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = VariantSerializer<SeqSerializer>;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = VariantSerializer<MapSerializer>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Value::Integer(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        let v = i64::try_from(v).map_err(|_| unsupported("An integer above i64::MAX"))?;
+        self.serialize_i64(v)
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("A float"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Self::Ok, Self::Error> {
+        Err(unsupported("A float"))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Value::Bytes(v.to_vec())))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        Ok(wrap_variant(variant, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(SeqSerializer {
+            items: Vec::with_capacity(len.unwrap_or_default()),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(VariantSerializer {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(MapSerializer {
+            entries: BTreeMap::new(),
+            next_key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(VariantSerializer {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+pub struct SeqSerializer {
+    items: Vec<Value>,
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Option<Value>;
+    type Error = BencodeSerializerError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let value = value
+            .serialize(BencodeSerializer)?
+            .ok_or_else(|| unsupported("A unit or None list element"))?;
+        self.items.push(value);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Value::List(self.items)))
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Option<Value>;
+    type Error = BencodeSerializerError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Option<Value>;
+    type Error = BencodeSerializerError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+pub struct MapSerializer {
+    entries: BTreeMap<Value, Value>,
+    next_key: Option<Value>,
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Option<Value>;
+    type Error = BencodeSerializerError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+        match key.serialize(BencodeSerializer)? {
+            Some(key @ Value::Bytes(_)) => {
+                self.next_key = Some(key);
+                Ok(())
+            }
+            _ => Err(BencodeSerializerError::EncodeError(String::from(
+                "Dict key must be a byte string",
+            ))),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self
+            .next_key
+            .take()
+            .ok_or_else(|| ser::Error::custom("serialize_value called before serialize_key"))?;
+        if let Some(value) = value.serialize(BencodeSerializer)? {
+            self.entries.insert(key, value);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Value::Dict(self.entries)))
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Option<Value>;
+    type Error = BencodeSerializerError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        ser::SerializeMap::serialize_entry(self, key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+// Enum variants with data are written as a single entry dict keyed by the variant name.
+fn wrap_variant(variant: &'static str, value: Option<Value>) -> Option<Value> {
+    value.map(|value| Value::Dict(BTreeMap::from([(Value::from(variant), value)])))
+}
+
+pub struct VariantSerializer<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+impl ser::SerializeTupleVariant for VariantSerializer<SeqSerializer> {
+    type Ok = Option<Value>;
+    type Error = BencodeSerializerError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        ser::SerializeSeq::serialize_element(&mut self.inner, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(wrap_variant(
+            self.variant,
+            ser::SerializeSeq::end(self.inner)?,
+        ))
+    }
+}
+
+impl ser::SerializeStructVariant for VariantSerializer<MapSerializer> {
+    type Ok = Option<Value>;
+    type Error = BencodeSerializerError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        ser::SerializeMap::serialize_entry(&mut self.inner, key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(wrap_variant(
+            self.variant,
+            ser::SerializeMap::end(self.inner)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct File {
+        length: u64,
+        path: Vec<String>,
+    }
+
+    #[derive(Serialize)]
+    struct Info {
+        name: String,
+        #[serde(rename = "piece length")]
+        piece_length: u32,
+        #[serde(with = "serde_bytes")]
+        pieces: Vec<u8>,
+        private: Option<bool>,
+        length: Option<u64>,
+        files: Option<Vec<File>>,
+    }
+
+    #[derive(Serialize)]
+    enum Message {
+        Ping,
+        Have(u32),
+        Request { index: u32, begin: u32 },
+    }
+
+    #[tokio::test]
+    async fn serialize_info_dict() {
+        let input = Info {
+            name: String::from("directory"),
+            piece_length: 16384,
+            pieces: vec![b'a'; 20],
+            private: Some(true),
+            length: None,
+            files: Some(vec![
+                File {
+                    length: 10,
+                    path: vec![String::from("a"), String::from("b.txt")],
+                },
+                File {
+                    length: 20,
+                    path: vec![String::from("c.txt")],
+                },
+            ]),
+        };
+        let mut buf = Vec::new();
+        to_writer(&input, &mut buf).await.unwrap();
+        assert_eq!(
+            String::from_utf8(buf.clone()).unwrap(),
+            "d5:filesld6:lengthi10e4:pathl1:a5:b.txteed6:lengthi20e4:pathl5:c.txteee\
+             4:name9:directory12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaa\
+             7:privatei1ee"
+        );
+
+        let decoded = Value::decode(&mut buf.as_slice()).await.unwrap();
+        assert_eq!(decoded, to_value(&input).unwrap());
+    }
+
+    #[test]
+    fn serialize_enum_variants() {
+        assert_eq!(to_value(&Message::Ping).unwrap(), Value::from("Ping"));
+        assert_eq!(
+            to_value(&Message::Have(3)).unwrap(),
+            Value::Dict(BTreeMap::from([(Value::from("Have"), Value::Integer(3))]))
+        );
+        assert_eq!(
+            to_value(&Message::Request { index: 1, begin: 2 }).unwrap(),
+            Value::Dict(BTreeMap::from([(
+                Value::from("Request"),
+                Value::Dict(BTreeMap::from([
+                    (Value::from("begin"), Value::Integer(2)),
+                    (Value::from("index"), Value::Integer(1)),
+                ]))
+            )]))
+        );
+    }
+
+    #[test]
+    fn serialize_byte_vectors() {
+        #[derive(Serialize)]
+        struct Hashes {
+            #[serde(with = "serde_bytes")]
+            bytes: Vec<u8>,
+            list: Vec<u8>,
+        }
+        let value = to_value(&Hashes {
+            bytes: vec![1, 2],
+            list: vec![1, 2],
+        })
+        .unwrap();
+        assert_eq!(
+            value,
+            Value::Dict(BTreeMap::from([
+                (Value::from("bytes"), Value::Bytes(vec![1, 2])),
+                (
+                    Value::from("list"),
+                    Value::List(vec![Value::Integer(1), Value::Integer(2)])
+                ),
+            ]))
+        );
+    }
+
+    #[test]
+    fn serialize_unit_in_sequence() {
+        assert!(to_value(&vec![Some(1), None]).is_err());
+        assert!(to_value(&(1, ())).is_err());
+        assert!(to_value(&Message::Have(1)).is_ok());
+    }
+
+    #[test]
+    fn serialize_unsupported() {
+        assert!(to_value(&1.5).is_err());
+        assert!(to_value(&u64::MAX).is_err());
+        assert!(to_value(&None::<u32>).is_err());
+        assert!(to_value(&BTreeMap::from([(1, 2)])).is_err());
+    }
+}
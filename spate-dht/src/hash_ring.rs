@@ -0,0 +1,151 @@
+use crate::{DhtNode, NodeId, NODE_ID_BITS};
+use std::{collections::BTreeMap, net::SocketAddr};
+
+// The operations a DHT client needs from its routing table, so implementations can be
+// swapped behind a `Box<dyn RoutingTable>`.
+pub trait RoutingTable {
+    fn insert(&mut self, node: DhtNode);
+
+    fn remove(&mut self, node_id: &NodeId) -> Option<DhtNode>;
+
+    // Up to `k` nodes, closest to `target` first.
+    fn find_closest(&self, target: &NodeId, k: usize) -> Vec<DhtNode>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// Keeps nodes sorted by ID. Every ID prefix is then a contiguous range, so the nodes
+// closest by XOR distance are found by walking the implied binary trie nearest half first,
+// each step being a single O(log n) range query.
+#[derive(Debug, Default)]
+pub struct HashRing {
+    nodes: BTreeMap<NodeId, SocketAddr>,
+}
+
+impl HashRing {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn collect(
+        &self,
+        target: &NodeId,
+        prefix: NodeId,
+        depth: usize,
+        k: usize,
+        out: &mut Vec<DhtNode>,
+    ) {
+        let remaining = k - out.len();
+        if remaining == 0 {
+            return;
+        }
+        let lo = (depth..NODE_ID_BITS).fold(prefix, |id, i| id.with_bit(i, false));
+        let hi = (depth..NODE_ID_BITS).fold(prefix, |id, i| id.with_bit(i, true));
+        let mut nodes: Vec<_> = self
+            .nodes
+            .range(lo..=hi)
+            .take(remaining + 1)
+            .map(|(id, addr)| DhtNode {
+                id: *id,
+                addr: *addr,
+            })
+            .collect();
+        // A range that fits whole needs no further splitting. At full depth the range
+        // holds at most one node, so this also ends the recursion.
+        if nodes.len() <= remaining {
+            nodes.sort_by_key(|node| node.id.distance(target));
+            out.extend(nodes);
+            return;
+        }
+        let bit = target.bit(depth);
+        self.collect(target, prefix.with_bit(depth, bit), depth + 1, k, out);
+        self.collect(target, prefix.with_bit(depth, !bit), depth + 1, k, out);
+    }
+}
+
+impl RoutingTable for HashRing {
+    fn insert(&mut self, node: DhtNode) {
+        self.nodes.insert(node.id, node.addr);
+    }
+
+    fn remove(&mut self, node_id: &NodeId) -> Option<DhtNode> {
+        self.nodes
+            .remove(node_id)
+            .map(|addr| DhtNode { id: *node_id, addr })
+    }
+
+    fn find_closest(&self, target: &NodeId, k: usize) -> Vec<DhtNode> {
+        let mut out = Vec::with_capacity(k.min(self.nodes.len()));
+        self.collect(target, *target, 0, k, &mut out);
+        out
+    }
+
+    fn len(&self) -> usize {
+        self.nodes.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, SocketAddrV4};
+
+    // Spreads `i` over the whole ID with multiplicative hashing, so the IDs are scattered
+    // across the ring but the same on every run.
+    fn node_id(i: u32) -> NodeId {
+        let mut id = [0; 20];
+        for (j, chunk) in id.chunks_mut(4).enumerate() {
+            let word = (i ^ ((j as u32) << 24)).wrapping_mul(0x9e37_79b1);
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        NodeId(id)
+    }
+
+    fn node(id: NodeId, port: u16) -> DhtNode {
+        DhtNode {
+            id,
+            addr: SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, port)),
+        }
+    }
+
+    #[test]
+    fn find_closest_matches_brute_force() {
+        let mut ring = HashRing::new();
+        let mut all = Vec::new();
+        for port in 0..2000 {
+            let node = node(node_id(port as u32), port);
+            ring.insert(node);
+            all.push(node);
+        }
+        assert_eq!(ring.len(), 2000);
+        for i in 0..50 {
+            let target = node_id(100_000 + i);
+            all.sort_by_key(|node| node.id.distance(&target));
+            assert_eq!(ring.find_closest(&target, 8), all[..8]);
+        }
+        let target = all[0].id;
+        assert_eq!(ring.find_closest(&target, 1), vec![all[0]]);
+        assert_eq!(ring.find_closest(&target, 5000).len(), 2000);
+    }
+
+    #[test]
+    fn remove_node() {
+        let mut ring = HashRing::new();
+        let near = node(NodeId([0; 20]), 1);
+        let far = node(NodeId([0xff; 20]), 2);
+        ring.insert(near);
+        ring.insert(far);
+        assert_eq!(ring.remove(&near.id), Some(near));
+        assert_eq!(ring.remove(&near.id), None);
+        assert_eq!(ring.find_closest(&NodeId([0; 20]), 8), vec![far]);
+
+        let table: &mut dyn RoutingTable = &mut ring;
+        table.remove(&far.id);
+        assert!(table.is_empty());
+        assert!(table.find_closest(&NodeId([0; 20]), 8).is_empty());
+    }
+}
@@ -0,0 +1,9 @@
+mod hash_ring;
+mod lookup;
+mod node;
+mod peer_store;
+
+pub use hash_ring::*;
+pub use lookup::*;
+pub use node::*;
+pub use peer_store::*;
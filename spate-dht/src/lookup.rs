@@ -0,0 +1,213 @@
+use crate::{DhtNode, NodeId};
+use std::{collections::BTreeMap, net::SocketAddr};
+
+// Only the queries a lookup sends; responses arrive as the nodes they contain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KrpcQuery {
+    FindNode { target: NodeId },
+    GetPeers { info_hash: [u8; 20] },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum QueryState {
+    NotQueried,
+    InFlight,
+    Responded,
+    Failed,
+}
+
+// The BEP-5 iterative lookup: keep querying the closest nodes seen so far, at most `alpha`
+// at a time, until the `k` closest have all answered.
+#[derive(Debug)]
+pub struct DhtIterativeLookup {
+    query: KrpcQuery,
+    target: NodeId,
+    k: usize,
+    alpha: usize,
+    // Keyed by distance to the target, so iteration is closest first.
+    candidates: BTreeMap<[u8; 20], (DhtNode, QueryState)>,
+}
+
+impl DhtIterativeLookup {
+    pub fn new(target: NodeId, k: usize, alpha: usize) -> Self {
+        Self::with_query(KrpcQuery::FindNode { target }, target, k, alpha)
+    }
+
+    pub fn get_peers(info_hash: [u8; 20], k: usize, alpha: usize) -> Self {
+        Self::with_query(
+            KrpcQuery::GetPeers { info_hash },
+            NodeId(info_hash),
+            k,
+            alpha,
+        )
+    }
+
+    fn with_query(query: KrpcQuery, target: NodeId, k: usize, alpha: usize) -> Self {
+        Self {
+            query,
+            target,
+            k,
+            alpha,
+            candidates: BTreeMap::new(),
+        }
+    }
+
+    // Starting points for the lookup, usually the closest nodes from the routing table.
+    pub fn add_nodes(&mut self, nodes: impl IntoIterator<Item = DhtNode>) {
+        for node in nodes {
+            self.candidates
+                .entry(node.id.distance(&self.target))
+                .or_insert((node, QueryState::NotQueried));
+        }
+    }
+
+    // Records the nodes each responder returned and returns the queries to send next.
+    pub fn step(&mut self, responses: Vec<(NodeId, Vec<DhtNode>)>) -> Vec<(SocketAddr, KrpcQuery)> {
+        for (id, nodes) in responses {
+            if let Some((_, state)) = self.candidates.get_mut(&id.distance(&self.target)) {
+                if *state == QueryState::InFlight {
+                    *state = QueryState::Responded;
+                }
+            }
+            self.add_nodes(nodes);
+        }
+        let available = self.alpha.saturating_sub(self.count(QueryState::InFlight));
+        let query = self.query;
+        self.closest_mut()
+            .filter(|(_, state)| *state == QueryState::NotQueried)
+            .take(available)
+            .map(|(node, state)| {
+                *state = QueryState::InFlight;
+                (node.addr, query)
+            })
+            .collect()
+    }
+
+    // A node that never answered no longer counts towards the k closest.
+    pub fn timed_out(&mut self, id: &NodeId) {
+        if let Some((_, state)) = self.candidates.get_mut(&id.distance(&self.target)) {
+            *state = QueryState::Failed;
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.count(QueryState::InFlight) == 0
+            && self
+                .closest()
+                .all(|(_, state)| *state == QueryState::Responded)
+    }
+
+    // The k closest nodes that answered, closest first.
+    pub fn result(&self) -> Vec<DhtNode> {
+        self.closest()
+            .filter(|(_, state)| *state == QueryState::Responded)
+            .map(|(node, _)| *node)
+            .collect()
+    }
+
+    fn closest(&self) -> impl Iterator<Item = &(DhtNode, QueryState)> {
+        self.candidates
+            .values()
+            .filter(|(_, state)| *state != QueryState::Failed)
+            .take(self.k)
+    }
+
+    fn closest_mut(&mut self) -> impl Iterator<Item = &mut (DhtNode, QueryState)> {
+        self.candidates
+            .values_mut()
+            .filter(|(_, state)| *state != QueryState::Failed)
+            .take(self.k)
+    }
+
+    fn count(&self, state: QueryState) -> usize {
+        self.candidates
+            .values()
+            .filter(|(_, s)| *s == state)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        collections::HashMap,
+        net::{Ipv4Addr, SocketAddrV4},
+    };
+
+    const TARGET: NodeId = NodeId([0; 20]);
+
+    // Nodes at a higher level are closer to the target: their ID starts with 0x80 >> level.
+    fn node(level: u8, i: u8) -> DhtNode {
+        let mut id = [0; 20];
+        id[0] = 0x80 >> level;
+        id[19] = i;
+        DhtNode {
+            id: NodeId(id),
+            addr: SocketAddr::V4(SocketAddrV4::new(
+                Ipv4Addr::LOCALHOST,
+                u16::from(level) * 100 + u16::from(i),
+            )),
+        }
+    }
+
+    #[test]
+    fn lookup_converges_on_closest_nodes() {
+        // Each node knows three nodes one level closer, up to level 2 which knows no closer.
+        let mut network = HashMap::new();
+        for level in 0..3 {
+            for i in 0..3 {
+                let next = (level + 1).min(2);
+                network.insert(
+                    node(level, i).addr,
+                    (
+                        node(level, i).id,
+                        (0..3).map(|j| node(next, j)).collect::<Vec<_>>(),
+                    ),
+                );
+            }
+        }
+
+        let mut lookup = DhtIterativeLookup::new(TARGET, 3, 2);
+        lookup.add_nodes((0..3).map(|i| node(0, i)));
+        let mut responses = vec![];
+        let mut rounds = 0;
+        while !lookup.is_complete() {
+            let queries = lookup.step(responses);
+            assert!(queries.len() <= 2);
+            assert!(queries
+                .iter()
+                .all(|(_, query)| *query == KrpcQuery::FindNode { target: TARGET }));
+            responses = queries
+                .iter()
+                .map(|(addr, _)| network[addr].clone())
+                .collect();
+            rounds += 1;
+            assert!(rounds < 20, "lookup did not terminate");
+        }
+        assert_eq!(
+            lookup.result(),
+            (0..3).map(|i| node(2, i)).collect::<Vec<_>>()
+        );
+        assert!(lookup.step(vec![]).is_empty());
+    }
+
+    #[test]
+    fn timed_out_nodes_are_replaced() {
+        let mut lookup = DhtIterativeLookup::get_peers([0; 20], 1, 1);
+        lookup.add_nodes([node(1, 0), node(0, 0)]);
+        let queries = lookup.step(vec![]);
+        assert_eq!(
+            queries,
+            vec![(node(1, 0).addr, KrpcQuery::GetPeers { info_hash: [0; 20] })]
+        );
+        assert!(lookup.step(vec![]).is_empty());
+
+        lookup.timed_out(&node(1, 0).id);
+        assert_eq!(lookup.step(vec![]).len(), 1);
+        assert!(!lookup.is_complete());
+        lookup.step(vec![(node(0, 0).id, vec![])]);
+        assert!(lookup.is_complete());
+        assert_eq!(lookup.result(), vec![node(0, 0)]);
+    }
+}
@@ -0,0 +1,33 @@
+use std::net::SocketAddr;
+
+pub const NODE_ID_BITS: usize = 160;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(pub [u8; 20]);
+
+impl NodeId {
+    // The XOR metric from BEP-5; comparing the results orders nodes by closeness.
+    pub fn distance(&self, other: &NodeId) -> [u8; 20] {
+        std::array::from_fn(|i| self.0[i] ^ other.0[i])
+    }
+
+    // Bit 0 is the most significant bit of the ID.
+    pub fn bit(&self, i: usize) -> bool {
+        self.0[i / 8] & (0x80 >> (i % 8)) != 0
+    }
+
+    pub fn with_bit(mut self, i: usize, set: bool) -> Self {
+        if set {
+            self.0[i / 8] |= 0x80 >> (i % 8);
+        } else {
+            self.0[i / 8] &= !(0x80 >> (i % 8));
+        }
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DhtNode {
+    pub id: NodeId,
+    pub addr: SocketAddr,
+}
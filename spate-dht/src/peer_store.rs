@@ -0,0 +1,115 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::SocketAddr,
+    time::Duration,
+};
+use tokio::time::Instant;
+
+// The number of peers handed out in a single get_peers response.
+const MAX_PEERS_RETURNED: usize = 8;
+
+// Remembers which peers announced each info hash so get_peers queries can be answered.
+pub struct DhtPeerStore {
+    max_per_infohash: usize,
+    ttl: Duration,
+    peers: HashMap<[u8; 20], VecDeque<(SocketAddr, Instant)>>,
+}
+
+impl DhtPeerStore {
+    pub fn new(max_per_infohash: usize, ttl: Duration) -> Self {
+        Self {
+            max_per_infohash,
+            ttl,
+            peers: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, info_hash: [u8; 20], peer: SocketAddr) {
+        let peers = self.peers.entry(info_hash).or_default();
+        // A repeated announce refreshes the peer, keeping the queue ordered oldest first.
+        peers.retain(|(addr, _)| *addr != peer);
+        peers.push_back((peer, Instant::now()));
+        while peers.len() > self.max_per_infohash {
+            peers.pop_front();
+        }
+    }
+
+    pub fn get(&mut self, info_hash: &[u8; 20]) -> Vec<SocketAddr> {
+        let Some(peers) = self.peers.get_mut(info_hash) else {
+            return vec![];
+        };
+        let now = Instant::now();
+        while let Some((_, seen)) = peers.front() {
+            if now.duration_since(*seen) < self.ttl {
+                break;
+            }
+            peers.pop_front();
+        }
+        if peers.is_empty() {
+            self.peers.remove(info_hash);
+            return vec![];
+        }
+        peers
+            .iter()
+            .rev()
+            .take(MAX_PEERS_RETURNED)
+            .map(|(addr, _)| *addr)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, SocketAddrV4};
+
+    const INFO_HASH: [u8; 20] = [1; 20];
+
+    fn peer(port: u16) -> SocketAddr {
+        SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, port))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn get_returns_most_recent_peers() {
+        let mut store = DhtPeerStore::new(100, Duration::from_secs(60));
+        for port in 0..20 {
+            store.insert(INFO_HASH, peer(port));
+        }
+        assert_eq!(
+            store.get(&INFO_HASH),
+            (12..20).rev().map(peer).collect::<Vec<_>>()
+        );
+        assert!(store.get(&[2; 20]).is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn get_evicts_expired_peers() {
+        let mut store = DhtPeerStore::new(100, Duration::from_secs(60));
+        for port in 0..20 {
+            store.insert(INFO_HASH, peer(port));
+        }
+        tokio::time::advance(Duration::from_secs(61)).await;
+        assert!(store.get(&INFO_HASH).is_empty());
+        assert!(store.peers.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn insert_refreshes_existing_peer() {
+        let mut store = DhtPeerStore::new(100, Duration::from_secs(60));
+        store.insert(INFO_HASH, peer(1));
+        store.insert(INFO_HASH, peer(2));
+        tokio::time::advance(Duration::from_secs(40)).await;
+        store.insert(INFO_HASH, peer(1));
+        tokio::time::advance(Duration::from_secs(40)).await;
+        assert_eq!(store.get(&INFO_HASH), vec![peer(1)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn insert_caps_peers_per_infohash() {
+        let mut store = DhtPeerStore::new(3, Duration::from_secs(60));
+        for port in 0..5 {
+            store.insert(INFO_HASH, peer(port));
+        }
+        assert_eq!(store.get(&INFO_HASH), vec![peer(4), peer(3), peer(2)]);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::MetaInfoError;
+use std::fmt::Write;
+use url::{ParseError, Url};
+
+const TRACKER_SCHEMES: &[&str] = &["http", "https", "udp", "ws"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackerUrlError {
+    UnknownScheme(String),
+    EmptyHost,
+    InvalidPort,
+    MalformedUrl(String),
+}
+
+impl std::fmt::Display for TrackerUrlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownScheme(arg0) => write!(f, "Unknown tracker scheme: {}", arg0),
+            Self::EmptyHost => write!(f, "Tracker URL has no host"),
+            Self::InvalidPort => write!(f, "Tracker URL port is out of range"),
+            Self::MalformedUrl(arg0) => write!(f, "Malformed tracker URL: {}", arg0),
+        }
+    }
+}
+
+impl std::error::Error for TrackerUrlError {}
+
+pub struct TrackerUrlValidator;
+
+impl TrackerUrlValidator {
+    pub fn validate(url: &str) -> Result<(), TrackerUrlError> {
+        let parsed = Url::parse(url).map_err(|e| match e {
+            ParseError::InvalidPort => TrackerUrlError::InvalidPort,
+            ParseError::EmptyHost => TrackerUrlError::EmptyHost,
+            e => TrackerUrlError::MalformedUrl(e.to_string()),
+        })?;
+        if !TRACKER_SCHEMES.contains(&parsed.scheme()) {
+            return Err(TrackerUrlError::UnknownScheme(parsed.scheme().to_string()));
+        }
+        if parsed.host_str().is_none_or(str::is_empty) {
+            return Err(TrackerUrlError::EmptyHost);
+        }
+        // Url::parse already rejects ports above 65535.
+        if parsed.port() == Some(0) {
+            return Err(TrackerUrlError::InvalidPort);
+        }
+        if parsed.path().is_empty() || parsed.path() == "/" {
+            return Err(TrackerUrlError::MalformedUrl(String::from("empty path")));
+        }
+        Ok(())
+    }
+}
+
+pub fn normalize_announce_url(raw: &[u8]) -> Result<String, MetaInfoError> {
+    let url = std::str::from_utf8(raw)
+        .map_err(|e| MetaInfoError::InvalidUrl(format!("announce URL is not UTF-8: {}", e)))?;
+    let mut encoded = String::with_capacity(url.len());
+    for c in url.chars() {
+        if c.is_ascii() {
+            encoded.push(c);
+        } else {
+            for b in c.encode_utf8(&mut [0; 4]).bytes() {
+                write!(encoded, "%{:02X}", b).unwrap();
+            }
+        }
+    }
+    // Parsing also lowercases the scheme and converts internationalised hosts to punycode.
+    Url::parse(&encoded)
+        .map(String::from)
+        .map_err(|e| MetaInfoError::InvalidUrl(format!("{}: {}", url, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_good_tracker_urls() {
+        for url in [
+            "http://tracker.example.com/announce",
+            "https://tracker.example.com:443/announce?passkey=abc",
+            "udp://tracker.opentrackr.org:1337/announce",
+            "ws://tracker.example.com:8000/announce",
+            "http://[2001:db8::1]:6969/announce",
+        ] {
+            assert_eq!(TrackerUrlValidator::validate(url), Ok(()), "{}", url);
+        }
+    }
+
+    #[test]
+    fn validate_bad_tracker_urls() {
+        let cases = [
+            (
+                "ftp://tracker.example.com/announce",
+                TrackerUrlError::UnknownScheme(String::from("ftp")),
+            ),
+            ("udp:///announce", TrackerUrlError::EmptyHost),
+            (
+                "udp://tracker.example.com:65536/announce",
+                TrackerUrlError::InvalidPort,
+            ),
+            (
+                "udp://tracker.example.com:0/announce",
+                TrackerUrlError::InvalidPort,
+            ),
+            (
+                "not a url",
+                TrackerUrlError::MalformedUrl(String::from("relative URL without a base")),
+            ),
+        ];
+        for (url, err) in cases {
+            assert_eq!(TrackerUrlValidator::validate(url), Err(err), "{}", url);
+        }
+        assert!(matches!(
+            TrackerUrlValidator::validate("udp://tracker.example.com:1337"),
+            Err(TrackerUrlError::MalformedUrl(_))
+        ));
+    }
+
+    #[test]
+    fn normalize_ascii_url() {
+        assert_eq!(
+            normalize_announce_url(b"HTTP://tracker.example.com:6969/announce").unwrap(),
+            "http://tracker.example.com:6969/announce"
+        );
+    }
+
+    #[test]
+    fn normalize_cyrillic_host() {
+        assert_eq!(
+            normalize_announce_url("http://трекер.рф/announce".as_bytes()).unwrap(),
+            "http://xn--e1aaowdh.xn--p1ai/announce"
+        );
+    }
+
+    #[test]
+    fn normalize_non_ascii_path() {
+        assert_eq!(
+            normalize_announce_url("udp://tracker.example.com:80/анонс".as_bytes()).unwrap(),
+            "udp://tracker.example.com:80/%D0%B0%D0%BD%D0%BE%D0%BD%D1%81"
+        );
+    }
+
+    #[test]
+    fn normalize_keeps_percent_encoding() {
+        assert_eq!(
+            normalize_announce_url(b"https://tracker.example.com/announce?key=a%20b%2F").unwrap(),
+            "https://tracker.example.com/announce?key=a%20b%2F"
+        );
+    }
+
+    #[test]
+    fn normalize_rejects_invalid_utf8() {
+        assert!(matches!(
+            normalize_announce_url(b"http://tracker.example.com/\xff\xfe"),
+            Err(MetaInfoError::InvalidUrl(_))
+        ));
+    }
+
+    #[test]
+    fn normalize_rejects_relative_url() {
+        assert!(normalize_announce_url(b"/announce").is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use crate::{MetaInfo, MetaInfoFileMode};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileInfo {
+    // Where the file lives relative to the download directory, including the torrent's
+    // directory name for multi-file torrents.
+    pub path: PathBuf,
+    pub length: u64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FileDiff {
+    pub added: Vec<FileInfo>,
+    pub removed: Vec<FileInfo>,
+    pub unchanged: Vec<FileInfo>,
+    // The file as it is in the newer torrent, with its previous length.
+    pub size_changed: Vec<(FileInfo, u64)>,
+}
+
+impl MetaInfo {
+    pub fn files(&self) -> Vec<FileInfo> {
+        match self.info.files {
+            MetaInfoFileMode::SingleFile(ref file) => vec![FileInfo {
+                path: PathBuf::from(&file.file_name),
+                length: file.length as u64,
+            }],
+            MetaInfoFileMode::MultiFile(ref files) => files
+                .files
+                .iter()
+                .map(|file| FileInfo {
+                    path: std::iter::once(&files.directory_name)
+                        .chain(&file.path)
+                        .collect(),
+                    length: file.length as u64,
+                })
+                .collect(),
+        }
+    }
+
+    // Compares the files of `self` with those of a newer version of the torrent, matching
+    // them up by path. The info hashes are not compared.
+    pub fn diff_files(&self, other: &MetaInfo) -> FileDiff {
+        let old = self.files();
+        let new = other.files();
+        let old_by_path: HashMap<&Path, &FileInfo> =
+            old.iter().map(|f| (f.path.as_path(), f)).collect();
+        let new_by_path: HashMap<&Path, &FileInfo> =
+            new.iter().map(|f| (f.path.as_path(), f)).collect();
+
+        // Both lists are walked in torrent order so the diff keeps that order.
+        let mut diff = FileDiff::default();
+        for file in &new {
+            match old_by_path.get(file.path.as_path()) {
+                None => diff.added.push(file.clone()),
+                Some(f) if f.length == file.length => diff.unchanged.push(file.clone()),
+                Some(f) => diff.size_changed.push((file.clone(), f.length)),
+            }
+        }
+        diff.removed = old
+            .iter()
+            .filter(|f| !new_by_path.contains_key(f.path.as_path()))
+            .cloned()
+            .collect();
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{self, multi_file_info, single_file_info};
+
+    const PIECE_LENGTH: i64 = 16 * 1024;
+
+    fn pieces(total: i64) -> usize {
+        (total as usize).div_ceil(PIECE_LENGTH as usize) * 20
+    }
+
+    fn meta(files: Option<&[(&str, i64)]>) -> MetaInfo {
+        let Some(files) = files else {
+            let length = 2 * PIECE_LENGTH;
+            return test_util::meta(single_file_info(
+                "ubuntu",
+                PIECE_LENGTH,
+                length,
+                pieces(length),
+            ));
+        };
+        let paths: Vec<Vec<&str>> = files
+            .iter()
+            .map(|(path, _)| path.split('/').collect())
+            .collect();
+        let files: Vec<_> = paths
+            .iter()
+            .zip(files)
+            .map(|(path, (_, length))| (path.as_slice(), *length))
+            .collect();
+        let total = files.iter().map(|(_, length)| length).sum();
+        test_util::meta(multi_file_info(
+            "ubuntu",
+            PIECE_LENGTH,
+            &files,
+            pieces(total),
+        ))
+    }
+
+    #[test]
+    fn diff_single_against_multi_file() {
+        let single = meta(None);
+        let multi = meta(Some(&[("ubuntu.iso", PIECE_LENGTH), ("SHA256SUMS", 100)]));
+        let diff = single.diff_files(&multi);
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.added.len(), 2);
+        assert!(diff.unchanged.is_empty());
+        assert_eq!(diff.removed[0].path, PathBuf::from("ubuntu"));
+        assert_eq!(diff.added[0].path, PathBuf::from("ubuntu/ubuntu.iso"));
+    }
+
+    #[test]
+    fn diff_multi_file_versions() {
+        let old = meta(Some(&[("a", 100), ("b/c", 200), ("d", 300)]));
+        let new = meta(Some(&[("a", 100), ("b/c", 250), ("e", 400)]));
+        let diff = old.diff_files(&new);
+        let file = |path: &str, length| FileInfo {
+            path: PathBuf::from(path),
+            length,
+        };
+        assert_eq!(diff.unchanged, vec![file("ubuntu/a", 100)]);
+        assert_eq!(diff.size_changed, vec![(file("ubuntu/b/c", 250), 200)]);
+        assert_eq!(diff.added, vec![file("ubuntu/e", 400)]);
+        assert_eq!(diff.removed, vec![file("ubuntu/d", 300)]);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::{MetaInfo, MetaInfoFileMode};
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HybridInfoHash {
+    V1([u8; 20]),
+    V2([u8; 32]),
+    // BEP-52 hybrid torrents carry both v1 piece hashes and a v2 file tree.
+    Hybrid { v1: [u8; 20], v2: [u8; 32] },
+}
+
+impl HybridInfoHash {
+    pub fn v1(&self) -> Option<&[u8; 20]> {
+        match self {
+            Self::V1(v1) | Self::Hybrid { v1, .. } => Some(v1),
+            Self::V2(_) => None,
+        }
+    }
+
+    pub fn v2(&self) -> Option<&[u8; 32]> {
+        match self {
+            Self::V2(v2) | Self::Hybrid { v2, .. } => Some(v2),
+            Self::V1(_) => None,
+        }
+    }
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            encoded.push(b as char);
+        } else {
+            write!(encoded, "%{:02X}", b).unwrap();
+        }
+    }
+    encoded
+}
+
+impl MetaInfo {
+    pub fn hybrid_info_hash(&self) -> HybridInfoHash {
+        match self.info_hash_v2 {
+            // A v2 torrent without v1 piece hashes can't be joined by v1 peers.
+            Some(v2) if self.info.is_v2_only() => HybridInfoHash::V2(v2),
+            Some(v2) => HybridInfoHash::Hybrid {
+                v1: self.info_hash,
+                v2,
+            },
+            None => HybridInfoHash::V1(self.info_hash),
+        }
+    }
+
+    pub fn magnet_uri(&self) -> String {
+        let name = match self.info.files {
+            MetaInfoFileMode::SingleFile(ref file) => &file.file_name,
+            MetaInfoFileMode::MultiFile(ref files) => &files.directory_name,
+        };
+        let hash = self.hybrid_info_hash();
+        let mut params = Vec::new();
+        if let Some(v1) = hash.v1() {
+            params.push(format!("xt=urn:btih:{}", hex(v1)));
+        }
+        // Multihash prefix: 0x12 is SHA-256, 0x20 the 32 byte digest length.
+        if let Some(v2) = hash.v2() {
+            params.push(format!("xt=urn:btmh:1220{}", hex(v2)));
+        }
+        params.push(format!("dn={}", percent_encode(name)));
+        for tracker in std::iter::once(&self.announce).chain(self.announce_list.iter().flatten()) {
+            let param = format!("tr={}", percent_encode(tracker));
+            if !params.contains(&param) {
+                params.push(param);
+            }
+        }
+        format!("magnet:?{}", params.join("&"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{meta, single_file_info};
+    use sha1::{Digest, Sha1};
+    use sha2::Sha256;
+    use spate_bencode::Value;
+    use std::collections::BTreeMap;
+
+    fn info(meta_version: Option<i64>) -> Value {
+        let Value::Dict(mut info) = single_file_info("hybrid.bin", 16384, 16384, 20) else {
+            unreachable!();
+        };
+        if let Some(version) = meta_version {
+            info.insert(Value::from("meta version"), Value::Integer(version));
+            info.insert(
+                Value::from("file tree"),
+                Value::Dict(BTreeMap::from([(
+                    Value::from("hybrid.bin"),
+                    Value::Dict(BTreeMap::from([(
+                        Value::from(""),
+                        Value::Dict(BTreeMap::from([
+                            (Value::from("length"), Value::Integer(16384)),
+                            (Value::from("pieces root"), Value::Bytes(vec![2; 32])),
+                        ])),
+                    )])),
+                )])),
+            );
+        }
+        Value::Dict(info)
+    }
+
+    #[test]
+    fn hybrid_torrent_has_both_hashes() {
+        let encoded = info(Some(2)).to_bytes();
+        let meta = meta(info(Some(2)));
+        let v1: [u8; 20] = Sha1::digest(&encoded).into();
+        let v2: [u8; 32] = Sha256::digest(&encoded).into();
+        assert_eq!(meta.hybrid_info_hash(), HybridInfoHash::Hybrid { v1, v2 });
+        assert_eq!(
+            meta.magnet_uri(),
+            format!(
+                "magnet:?xt=urn:btih:{}&xt=urn:btmh:1220{}&dn=hybrid.bin\
+                 &tr=http%3A%2F%2Ftracker%2Fannounce",
+                hex(&v1),
+                hex(&v2)
+            )
+        );
+    }
+
+    #[test]
+    fn v1_torrent_hash() {
+        let meta = meta(info(None));
+        assert_eq!(
+            meta.hybrid_info_hash(),
+            HybridInfoHash::V1(Sha1::digest(info(None).to_bytes()).into())
+        );
+        assert!(!meta.magnet_uri().contains("btmh"));
+    }
+
+    #[test]
+    fn v2_only_torrent() {
+        let leaf = |length| {
+            Value::Dict(BTreeMap::from([(
+                Value::from(""),
+                Value::Dict(BTreeMap::from([
+                    (Value::from("length"), Value::Integer(length)),
+                    (Value::from("pieces root"), Value::Bytes(vec![2; 32])),
+                ])),
+            )]))
+        };
+        let info = Value::Dict(BTreeMap::from([
+            (Value::from("name"), Value::from("v2")),
+            (Value::from("piece length"), Value::Integer(16384)),
+            (Value::from("meta version"), Value::Integer(2)),
+            (
+                Value::from("file tree"),
+                Value::Dict(BTreeMap::from([
+                    (Value::from("a.bin"), leaf(20000)),
+                    (
+                        Value::from("dir"),
+                        Value::Dict(BTreeMap::from([(Value::from("b.bin"), leaf(5))])),
+                    ),
+                ])),
+            ),
+        ]));
+        let encoded = info.to_bytes();
+        let meta = meta(info);
+        let v2: [u8; 32] = Sha256::digest(&encoded).into();
+        assert_eq!(meta.hybrid_info_hash(), HybridInfoHash::V2(v2));
+        assert!(!meta.magnet_uri().contains("btih"));
+        assert_eq!(meta.info.total_length(), 20005);
+        let MetaInfoFileMode::MultiFile(ref files) = meta.info.files else {
+            panic!("expected a multi file torrent");
+        };
+        assert_eq!(files.files[1].path, vec!["dir", "b.bin"]);
+    }
+
+    #[tokio::test]
+    async fn info_hash_uses_original_bytes() {
+        // `name` sorts after `length`, so re-encoding would reorder the keys.
+        let info =
+            b"d4:name1:a6:lengthi16384e12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaae";
+        let mut data = b"d8:announce23:http://tracker/announce4:info".to_vec();
+        data.extend_from_slice(info);
+        data.push(b'e');
+
+        let meta = MetaInfo::from_bytes(&data).await.unwrap();
+        let expected: [u8; 20] = Sha1::digest(info).into();
+        assert_eq!(meta.info_hash, expected);
+
+        let value = Value::decode(&mut data.as_slice()).await.unwrap();
+        assert_ne!(MetaInfo::try_from(&value).unwrap().info_hash, expected);
+    }
+
+    #[tokio::test]
+    async fn ubuntu_info_hash() {
+        let data = include_bytes!("../../spate/resources/ubuntu-23.10.1-desktop-amd64.iso.torrent");
+        let meta = MetaInfo::from_bytes(data).await.unwrap();
+        assert_eq!(
+            hex(&meta.info_hash),
+            "9ecd4676fd0f0474151a4b74a5958f42639cebdf"
+        );
+        assert_eq!(meta.info_hash_v2, None);
+    }
+}
@@ -1,39 +1,182 @@
 #[macro_use]
 extern crate lazy_static;
 
+mod announce;
+mod diff;
+mod hash;
+mod name;
+mod paths;
+mod piece;
+#[cfg(test)]
+pub(crate) mod test_util;
+
+pub use announce::*;
 use anyhow::Error;
+pub use diff::*;
+pub use hash::*;
+pub use name::*;
+pub use paths::*;
+pub use piece::*;
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
 use spate_bencode::Value;
-use std::convert::{Into, TryFrom};
+use std::{collections::BTreeMap, convert::TryFrom};
+use url::Url;
+
+// Pieces smaller than this are legal but almost always a sign of a broken torrent.
+const MIN_PIECE_LENGTH: i32 = 16 * 1024;
+const PIECE_HASH_LENGTH: usize = 20;
 
 lazy_static! {
     static ref ANNOUNCE_KEY: Value = Value::from("announce");
-    static ref ANNOUNCE_LIST_KEY: Value = Value::from("announce list");
+    static ref ANNOUNCE_LIST_KEY: Value = Value::from("announce-list");
+    static ref INFO_KEY: Value = Value::from("info");
+    static ref CREATION_DATE_KEY: Value = Value::from("creation date");
+    static ref COMMENT_KEY: Value = Value::from("comment");
+    static ref CREATED_BY_KEY: Value = Value::from("created by");
+    static ref ENCODING_KEY: Value = Value::from("encoding");
+    static ref PIECE_LENGTH_KEY: Value = Value::from("piece length");
+    static ref PIECES_KEY: Value = Value::from("pieces");
+    static ref PRIVATE_KEY: Value = Value::from("private");
+    static ref NAME_KEY: Value = Value::from("name");
+    static ref LENGTH_KEY: Value = Value::from("length");
+    static ref MD5SUM_KEY: Value = Value::from("md5sum");
+    static ref FILES_KEY: Value = Value::from("files");
+    static ref PATH_KEY: Value = Value::from("path");
+    static ref URL_LIST_KEY: Value = Value::from("url-list");
+    static ref META_VERSION_KEY: Value = Value::from("meta version");
+    static ref FILE_TREE_KEY: Value = Value::from("file tree");
+    static ref FILE_TREE_LEAF_KEY: Value = Value::from("");
 }
 
 #[derive(Debug)]
-pub struct MetaInfo<'a> {
+pub enum MetaInfoError {
+    InvalidField(String),
+    InvalidUrl(String),
+}
+
+impl std::fmt::Display for MetaInfoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidField(arg0) => write!(f, "Invalid field: {}", arg0),
+            Self::InvalidUrl(arg0) => write!(f, "Invalid URL: {}", arg0),
+        }
+    }
+}
+
+impl std::error::Error for MetaInfoError {}
+
+#[derive(Debug)]
+pub struct MetaInfo {
     // A dictionary that describes the file(s) of the torrent.
-    info: MetaInfoFiles,
+    pub info: MetaInfoFiles,
     // The announce URL of the tracker
-    announce: &'a str,
+    pub announce: String,
     // This is an extension to the official specification, offering backwards-compatibility.
-    announce_list: Option<Vec<&'a str>>,
+    pub announce_list: Option<Vec<String>>,
     // The creation time of the torrent, in standard UNIX epoch format (integer, seconds since 1-Jan-1970 00:00:00 UTC)
-    creation_date: Option<usize>,
+    pub creation_date: Option<usize>,
     // Free-form textual comments of the author
-    comment: Option<String>,
+    pub comment: Option<String>,
     // Name and version of the program used to create the torrent
-    created_by: Option<String>,
+    pub created_by: Option<String>,
     // The string encoding format used to generate the pieces part of the info dictionary
-    encoding: Option<String>,
+    pub encoding: Option<String>,
+    // BEP-19 WebSeed URLs serving the torrent's content over HTTP
+    pub url_list: Option<Vec<Url>>,
+    // SHA-1 of the encoded info dict
+    pub info_hash: [u8; 20],
+    // SHA-256 of the encoded info dict, only for BEP-52 torrents with meta version 2
+    pub info_hash_v2: Option<[u8; 32]>,
+}
+
+impl MetaInfo {
+    pub fn url_list_for_file(&self, file_index: usize) -> Vec<Url> {
+        let Some(ref urls) = self.url_list else {
+            return vec![];
+        };
+        let path = match self.info.files {
+            MetaInfoFileMode::SingleFile(_) if file_index == 0 => return urls.clone(),
+            MetaInfoFileMode::MultiFile(ref files) if file_index < files.files.len() => {
+                std::iter::once(&files.directory_name).chain(&files.files[file_index].path)
+            }
+            _ => return vec![],
+        };
+        urls.iter()
+            .filter_map(|url| {
+                let mut url = url.clone();
+                // Segments are percent-encoded individually, so names containing
+                // '?', '#' or '/' can't change the meaning of the URL.
+                url.path_segments_mut()
+                    .ok()?
+                    .pop_if_empty()
+                    .extend(path.clone());
+                Some(url)
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug)]
 pub struct MetaInfoFiles {
-    piece_length: i32,
-    pieces: Vec<String>,
-    private: bool,
-    files: MetaInfoFileMode,
+    pub piece_length: i32,
+    // The concatenated 20-byte SHA-1 hashes of every piece, empty for v2-only torrents.
+    pub pieces: Vec<u8>,
+    pub private: bool,
+    pub files: MetaInfoFileMode,
+    // 2 for BEP-52 torrents, which may leave out the v1 fields above.
+    pub meta_version: Option<i64>,
+}
+
+impl MetaInfoFiles {
+    pub fn is_v2_only(&self) -> bool {
+        self.meta_version == Some(2) && self.pieces.is_empty()
+    }
+
+    pub fn total_length(&self) -> usize {
+        match self.files {
+            MetaInfoFileMode::SingleFile(ref file) => file.length,
+            MetaInfoFileMode::MultiFile(ref files) => files.files.iter().map(|f| f.length).sum(),
+        }
+    }
+
+    pub fn validate_piece_boundaries(&self) -> Result<(), MetaInfoError> {
+        if self.piece_length < MIN_PIECE_LENGTH {
+            return Err(MetaInfoError::InvalidField(format!(
+                "piece length {} is smaller than {} bytes",
+                self.piece_length, MIN_PIECE_LENGTH
+            )));
+        }
+        if let MetaInfoFileMode::MultiFile(ref files) = self.files {
+            if let Some(file) = files.files.iter().find(|f| f.length == 0) {
+                return Err(MetaInfoError::InvalidField(format!(
+                    "file {} has a length of 0",
+                    file.path.join("/")
+                )));
+            }
+        }
+        let total_length = self.total_length();
+        if total_length == 0 {
+            return Err(MetaInfoError::InvalidField(String::from(
+                "total length must be greater than 0",
+            )));
+        }
+        // v2-only torrents hash their pieces per file, in `piece layers`.
+        if self.is_v2_only() {
+            return Ok(());
+        }
+        let expected = total_length.div_ceil(self.piece_length as usize) * PIECE_HASH_LENGTH;
+        if self.pieces.len() != expected {
+            return Err(MetaInfoError::InvalidField(format!(
+                "pieces is {} bytes long but {} bytes of piece length {} need {}",
+                self.pieces.len(),
+                total_length,
+                self.piece_length,
+                expected
+            )));
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -44,57 +187,242 @@ pub enum MetaInfoFileMode {
 
 #[derive(Debug)]
 pub struct MetaInfoSingleFile {
-    file_name: String,
-    length: usize,
-    md5sum: Option<String>,
+    pub file_name: String,
+    pub length: usize,
+    pub md5sum: Option<String>,
 }
 
 #[derive(Debug)]
 pub struct MetaInfoMultiFiles {
-    directory_name: String,
-    files: Vec<MetaInfoMultiFileEntry>,
+    pub directory_name: String,
+    pub files: Vec<MetaInfoMultiFileEntry>,
 }
 
 #[derive(Debug)]
 pub struct MetaInfoMultiFileEntry {
-    length: usize,
-    md5sum: Option<String>,
-    path: Vec<String>,
+    pub length: usize,
+    pub md5sum: Option<String>,
+    pub path: Vec<String>,
+}
+
+fn get<'a>(dict: &'a BTreeMap<Value, Value>, key: &Value) -> Result<&'a Value, Error> {
+    dict.get(key)
+        .ok_or_else(|| Error::msg(format!("{:?} key not found in dict", key)))
+}
+
+fn as_dict(value: &Value) -> Result<&BTreeMap<Value, Value>, Error> {
+    match value {
+        Value::Dict(ref d) => Ok(d),
+        _ => Err(Error::msg("expected dict")),
+    }
+}
+
+fn as_list(value: &Value) -> Result<&Vec<Value>, Error> {
+    match value {
+        Value::List(ref l) => Ok(l),
+        _ => Err(Error::msg("expected list")),
+    }
+}
+
+fn as_integer<T: TryFrom<i64>>(value: &Value) -> Result<T, Error> {
+    match value {
+        Value::Integer(i) => T::try_from(*i).map_err(|_| Error::msg("integer out of range")),
+        _ => Err(Error::msg("expected integer")),
+    }
+}
+
+fn as_bytes(value: &Value) -> Result<&[u8], Error> {
+    match value {
+        Value::Bytes(ref b) => Ok(b),
+        _ => Err(Error::msg("expected byte string")),
+    }
+}
+
+fn as_url(value: &Value) -> Result<String, Error> {
+    Ok(normalize_announce_url(as_bytes(value)?)?)
 }
 
-impl<'a> TryFrom<&'a Value> for MetaInfo<'a> {
+fn as_string(value: &Value) -> Result<String, Error> {
+    TryInto::<&str>::try_into(value).map(String::from)
+}
+
+fn optional<'a, T>(
+    dict: &'a BTreeMap<Value, Value>,
+    key: &Value,
+    f: impl FnOnce(&'a Value) -> Result<T, Error>,
+) -> Result<Option<T>, Error> {
+    dict.get(key).map(f).transpose()
+}
+
+impl TryFrom<&Value> for MetaInfoFiles {
     type Error = Error;
 
-    fn try_from(value: &'a Value) -> Result<Self, Self::Error> {
-        match value {
-            Value::Dict(dict) => Ok(Self {
-                info: MetaInfoFiles {
-                    piece_length: 0,
-                    pieces: vec![],
-                    private: false,
-                    files: MetaInfoFileMode::SingleFile(MetaInfoSingleFile {
-                        file_name: String::from("foo"),
-                        length: 0,
-                        md5sum: None,
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let dict = as_dict(value)?;
+        let name = as_string(get(dict, &NAME_KEY)?)?;
+        let meta_version = optional(dict, &META_VERSION_KEY, as_integer::<i64>)?;
+        let v2 = meta_version == Some(2);
+        let files = match dict.get(&FILES_KEY) {
+            Some(files) => MetaInfoFileMode::MultiFile(MetaInfoMultiFiles {
+                directory_name: name,
+                files: as_list(files)?
+                    .iter()
+                    .map(|file| {
+                        let file = as_dict(file)?;
+                        Ok(MetaInfoMultiFileEntry {
+                            length: as_integer(get(file, &LENGTH_KEY)?)?,
+                            md5sum: optional(file, &MD5SUM_KEY, as_string)?,
+                            path: as_list(get(file, &PATH_KEY)?)?
+                                .iter()
+                                .map(as_string)
+                                .collect::<Result<_, _>>()?,
+                        })
+                    })
+                    .collect::<Result<_, Error>>()?,
+            }),
+            None if v2 && !dict.contains_key(&LENGTH_KEY) => {
+                let mut files = Vec::new();
+                file_tree(get(dict, &FILE_TREE_KEY)?, &mut Vec::new(), &mut files)?;
+                match files.as_slice() {
+                    [file] if file.path == [name.as_str()] => {
+                        MetaInfoFileMode::SingleFile(MetaInfoSingleFile {
+                            file_name: name,
+                            length: file.length,
+                            md5sum: None,
+                        })
+                    }
+                    _ => MetaInfoFileMode::MultiFile(MetaInfoMultiFiles {
+                        directory_name: name,
+                        files,
                     }),
-                },
-                announce: dict
-                    .get(&ANNOUNCE_KEY)
-                    .expect("announce key not found in dict")
-                    .try_into()?,
-                announce_list: dict.get(&ANNOUNCE_LIST_KEY)?.try_into()?,
-                creation_date: Some(0),
-                comment: None,
-                created_by: None,
-                encoding: None,
+                }
+            }
+            None => MetaInfoFileMode::SingleFile(MetaInfoSingleFile {
+                file_name: name,
+                length: as_integer(get(dict, &LENGTH_KEY)?)?,
+                md5sum: optional(dict, &MD5SUM_KEY, as_string)?,
+            }),
+        };
+        let pieces = match dict.get(&PIECES_KEY) {
+            None if v2 => vec![],
+            pieces => as_bytes(pieces.ok_or_else(|| Error::msg("pieces key not found in dict"))?)?
+                .to_vec(),
+        };
+        Ok(Self {
+            piece_length: as_integer(get(dict, &PIECE_LENGTH_KEY)?)?,
+            pieces,
+            private: optional(dict, &PRIVATE_KEY, as_integer::<i64>)? == Some(1),
+            files,
+            meta_version,
+        })
+    }
+}
+
+// Flattens a BEP-52 file tree, where each file is a dict holding its details under the
+// empty key, e.g. `{"dir": {"file": {"": {"length": 1}}}}`.
+fn file_tree(
+    tree: &Value,
+    path: &mut Vec<String>,
+    files: &mut Vec<MetaInfoMultiFileEntry>,
+) -> Result<(), Error> {
+    for (name, node) in as_dict(tree)? {
+        path.push(as_string(name)?);
+        let node_dict = as_dict(node)?;
+        match node_dict.get(&FILE_TREE_LEAF_KEY) {
+            Some(file) => files.push(MetaInfoMultiFileEntry {
+                length: as_integer(get(as_dict(file)?, &LENGTH_KEY)?)?,
+                md5sum: None,
+                path: path.clone(),
             }),
-            _ => Err(Error::msg("expected dict")),
+            None => file_tree(node, path, files)?,
         }
+        path.pop();
     }
+    Ok(())
 }
 
-impl Into<Value> for MetaInfo<'_> {
-    fn into(self) -> Value {
+impl TryFrom<&Value> for MetaInfo {
+    type Error = Error;
+
+    // The info hashes are taken over the re-encoded info dict, which differs from the
+    // original for torrents that weren't canonically encoded. Prefer MetaInfo::from_bytes.
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let info = get(as_dict(value)?, &INFO_KEY)?;
+        Self::parse(value, &info.to_bytes())
+    }
+}
+
+impl MetaInfo {
+    pub async fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        let value = Value::decode(&mut &data[..]).await?;
+        Self::parse(&value, info_dict_bytes(data)?)
+    }
+
+    // `encoded_info` is the info dict as bencode, to compute the info hashes from.
+    fn parse(value: &Value, encoded_info: &[u8]) -> Result<Self, Error> {
+        let dict = as_dict(value)?;
+        let info = get(dict, &INFO_KEY)?;
+        let mut meta = Self {
+            info: MetaInfoFiles::try_from(info)?,
+            announce: as_url(get(dict, &ANNOUNCE_KEY)?)?,
+            // Flatten the BEP-12 tiers, keeping trackers in their announced order. Entries
+            // that aren't URLs at all are skipped, like the ones the validator warns about.
+            announce_list: optional(dict, &ANNOUNCE_LIST_KEY, |tiers| {
+                as_list(tiers)?
+                    .iter()
+                    .map(|tier| {
+                        Ok(as_list(tier)?
+                            .iter()
+                            .filter_map(|url| {
+                                as_url(url)
+                                    .map_err(|e| {
+                                        log::warn!(
+                                            "{:?}: {}",
+                                            url,
+                                            TrackerUrlError::MalformedUrl(e.to_string())
+                                        )
+                                    })
+                                    .ok()
+                            })
+                            .collect())
+                    })
+                    .collect::<Result<Vec<Vec<String>>, Error>>()
+                    .map(|tiers| tiers.concat())
+            })?,
+            creation_date: optional(dict, &CREATION_DATE_KEY, as_integer)?,
+            comment: optional(dict, &COMMENT_KEY, as_string)?,
+            created_by: optional(dict, &CREATED_BY_KEY, as_string)?,
+            encoding: optional(dict, &ENCODING_KEY, as_string)?,
+            // Either a single URL or a list of them; unusable entries are skipped.
+            url_list: optional(dict, &URL_LIST_KEY, |urls| {
+                let urls = match urls {
+                    Value::List(ref l) => l.iter().collect(),
+                    url => vec![url],
+                };
+                Ok(urls
+                    .into_iter()
+                    .filter_map(|url| Url::parse(as_string(url).ok()?.as_str()).ok())
+                    .collect())
+            })?,
+            info_hash: Sha1::digest(encoded_info).into(),
+            info_hash_v2: None,
+        };
+        if meta.info.meta_version == Some(2) {
+            meta.info_hash_v2 = Some(Sha256::digest(encoded_info).into());
+        }
+        meta.info.validate_piece_boundaries()?;
+        // Plenty of torrents carry junk in announce-list, so this is only worth a warning.
+        for url in std::iter::once(&meta.announce).chain(meta.announce_list.iter().flatten()) {
+            if let Err(e) = TrackerUrlValidator::validate(url) {
+                log::warn!("{}: {}", url, e);
+            }
+        }
+        Ok(meta)
+    }
+}
+
+impl From<MetaInfo> for Value {
+    fn from(_value: MetaInfo) -> Self {
         Value::Bytes("test".into())
     }
 }
@@ -102,10 +430,186 @@ impl Into<Value> for MetaInfo<'_> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::{self, multi_file_info, torrent};
+
+    fn single_file_info(piece_length: i64, length: i64, pieces: usize) -> Value {
+        test_util::single_file_info("file.iso", piece_length, length, pieces)
+    }
+
+    #[tokio::test]
+    async fn parse_ubuntu_torrent() {
+        let data = include_bytes!("../../spate/resources/ubuntu-23.10.1-desktop-amd64.iso.torrent");
+        let value = Value::decode(&mut &data[..]).await.unwrap();
+        let meta = MetaInfo::try_from(&value).unwrap();
+        assert_eq!(meta.announce, "https://torrent.ubuntu.com/announce");
+        assert_eq!(
+            meta.announce_list.unwrap(),
+            vec![
+                "https://torrent.ubuntu.com/announce",
+                "https://ipv6.torrent.ubuntu.com/announce"
+            ]
+        );
+        assert_eq!(meta.creation_date, Some(1697466120));
+        assert_eq!(meta.info.piece_length, 262144);
+        assert_eq!(meta.info.total_length(), 5173995520);
+    }
+
+    #[test]
+    fn normalize_announce_list() {
+        let value = Value::Dict(BTreeMap::from([
+            (
+                Value::from("announce"),
+                Value::from("HTTP://Tracker/announce"),
+            ),
+            (
+                Value::from("announce-list"),
+                Value::List(vec![
+                    Value::List(vec![Value::from("udp://tracker:80")]),
+                    Value::List(vec![Value::from("http://трекер.рф/announce")]),
+                ]),
+            ),
+            (Value::from("info"), single_file_info(16384, 1, 20)),
+        ]));
+        let meta = MetaInfo::try_from(&value).unwrap();
+        assert_eq!(meta.announce, "http://tracker/announce");
+        assert_eq!(
+            meta.announce_list.unwrap(),
+            vec!["udp://tracker:80", "http://xn--e1aaowdh.xn--p1ai/announce"]
+        );
+    }
+
+    #[test]
+    fn announce_list_skips_junk_entries() {
+        let value = Value::Dict(BTreeMap::from([
+            (
+                Value::from("announce"),
+                Value::from("http://tracker/announce"),
+            ),
+            (
+                Value::from("announce-list"),
+                Value::List(vec![
+                    Value::List(vec![
+                        Value::from("not a url"),
+                        Value::from("udp://tracker:80/announce"),
+                    ]),
+                    Value::List(vec![Value::Integer(1), Value::Bytes(vec![0xff])]),
+                    Value::List(vec![Value::from("https://backup/announce")]),
+                ]),
+            ),
+            (Value::from("info"), single_file_info(16384, 1, 20)),
+        ]));
+        let meta = MetaInfo::try_from(&value).unwrap();
+        assert_eq!(
+            meta.announce_list.unwrap(),
+            vec!["udp://tracker:80/announce", "https://backup/announce"]
+        );
+    }
+
+    #[test]
+    fn validate_single_file() {
+        let value = torrent(single_file_info(16384, 16385, 40));
+        assert!(MetaInfo::try_from(&value).is_ok());
+    }
+
+    #[test]
+    fn validate_multi_file() {
+        let info = MetaInfoFiles::try_from(&multi_file_info(
+            "directory",
+            16384,
+            &[(&["dir", "0"], 16384), (&["dir", "1"], 1)],
+            40,
+        ))
+        .unwrap();
+        assert!(info.validate_piece_boundaries().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_small_piece_length() {
+        let info = MetaInfoFiles::try_from(&single_file_info(8192, 8192, 20)).unwrap();
+        assert!(matches!(
+            info.validate_piece_boundaries(),
+            Err(MetaInfoError::InvalidField(_))
+        ));
+    }
+
+    #[test]
+    fn validate_rejects_empty_torrent() {
+        let info = MetaInfoFiles::try_from(&single_file_info(16384, 0, 0)).unwrap();
+        assert!(info.validate_piece_boundaries().is_err());
+    }
+
+    #[test]
+    fn validate_rejects_empty_file() {
+        let info = MetaInfoFiles::try_from(&multi_file_info(
+            "directory",
+            16384,
+            &[(&["dir", "0"], 16384), (&["dir", "1"], 0)],
+            20,
+        ))
+        .unwrap();
+        assert!(info.validate_piece_boundaries().is_err());
+    }
+
+    #[test]
+    fn validate_rejects_wrong_pieces_length() {
+        let value = torrent(single_file_info(16384, 16385, 20));
+        assert!(MetaInfo::try_from(&value).is_err());
+    }
+
+    fn web_seed_torrent(info: Value, url_list: Value) -> MetaInfo {
+        let mut value = torrent(info);
+        if let Value::Dict(ref mut dict) = value {
+            dict.insert(Value::from("url-list"), url_list);
+        }
+        MetaInfo::try_from(&value).unwrap()
+    }
+
+    #[test]
+    fn url_list_for_multi_file() {
+        let info = multi_file_info(
+            "dirname",
+            16384,
+            &[
+                (&["readme.txt"], 16384),
+                (&["subdir", "deeper", "my file #1.mkv"], 16384),
+            ],
+            40,
+        );
+        let meta = web_seed_torrent(
+            info,
+            Value::List(vec![
+                Value::from("http://example.com/seed/"),
+                Value::from("http://mirror.example.com/seed"),
+                Value::from("not a url"),
+            ]),
+        );
+        assert_eq!(
+            meta.url_list_for_file(1)
+                .iter()
+                .map(Url::as_str)
+                .collect::<Vec<_>>(),
+            vec![
+                "http://example.com/seed/dirname/subdir/deeper/my%20file%20%231.mkv",
+                "http://mirror.example.com/seed/dirname/subdir/deeper/my%20file%20%231.mkv",
+            ]
+        );
+        assert_eq!(
+            meta.url_list_for_file(0)[0].as_str(),
+            "http://example.com/seed/dirname/readme.txt"
+        );
+        assert!(meta.url_list_for_file(2).is_empty());
+    }
 
     #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
+    fn url_list_for_single_file() {
+        let meta = web_seed_torrent(
+            single_file_info(16384, 1, 20),
+            Value::from("http://example.com/file.iso"),
+        );
+        assert_eq!(
+            meta.url_list_for_file(0),
+            vec![Url::parse("http://example.com/file.iso").unwrap()]
+        );
+        assert!(meta.url_list_for_file(1).is_empty());
     }
 }
@@ -0,0 +1,147 @@
+use spate_bencode::DecodeError;
+
+// Reads `info.name` straight from the encoded torrent. Everything else, notably the
+// `pieces` blob, is skipped over without being copied.
+pub fn extract_torrent_name(data: &[u8]) -> Result<String, DecodeError> {
+    let mut scanner = Scanner { data, pos: 0 };
+    if !scanner.find_key(b"info")? || !scanner.find_key(b"name")? {
+        return Err(DecodeError::DECODER("info.name not found"));
+    }
+    let name = scanner.read_bytes()?;
+    String::from_utf8(name.to_vec()).map_err(|_| DecodeError::DECODER("name is not valid UTF-8"))
+}
+
+// The `info` dict exactly as it appears in the encoded torrent, which is what info hashes
+// are computed over. Re-encoding a decoded Value only gives the same bytes when the
+// torrent was canonically encoded to begin with.
+pub fn info_dict_bytes(data: &[u8]) -> Result<&[u8], DecodeError> {
+    let mut scanner = Scanner { data, pos: 0 };
+    if !scanner.find_key(b"info")? {
+        return Err(DecodeError::DECODER("info not found"));
+    }
+    let start = scanner.pos;
+    scanner.skip()?;
+    Ok(&data[start..scanner.pos])
+}
+
+struct Scanner<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn peek(&self) -> Result<u8, DecodeError> {
+        self.data
+            .get(self.pos)
+            .copied()
+            .ok_or(DecodeError::DECODER("Expected token"))
+    }
+
+    fn find(&self, token: u8) -> Result<usize, DecodeError> {
+        self.data[self.pos..]
+            .iter()
+            .position(|b| *b == token)
+            .map(|i| self.pos + i)
+            .ok_or(DecodeError::DECODER("Unexpected end of input"))
+    }
+
+    fn read_bytes(&mut self) -> Result<&'a [u8], DecodeError> {
+        let delim = self.find(b':')?;
+        let length = std::str::from_utf8(&self.data[self.pos..delim])
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .ok_or(DecodeError::DECODER("parse size failed"))?;
+        let end = delim
+            .checked_add(1 + length)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(DecodeError::DECODER("Unexpected end of input"))?;
+        self.pos = end;
+        Ok(&self.data[delim + 1..end])
+    }
+
+    fn skip(&mut self) -> Result<(), DecodeError> {
+        let mut depth = 0;
+        loop {
+            match self.peek()? {
+                b'i' => self.pos = self.find(b'e')? + 1,
+                b'l' | b'd' => {
+                    self.pos += 1;
+                    depth += 1;
+                }
+                b'e' if depth > 0 => {
+                    self.pos += 1;
+                    depth -= 1;
+                }
+                b'0'..=b'9' => {
+                    self.read_bytes()?;
+                }
+                byte => {
+                    return Err(DecodeError::UnknownToken {
+                        byte,
+                        offset: self.pos as u64,
+                    })
+                }
+            }
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    // Leaves the scanner at the value for `key` if the dict at the current position has it.
+    fn find_key(&mut self, key: &[u8]) -> Result<bool, DecodeError> {
+        if self.peek()? != b'd' {
+            return Err(DecodeError::DECODER("expected dict"));
+        }
+        self.pos += 1;
+        while self.peek()? != b'e' {
+            if self.read_bytes()? == key {
+                return Ok(true);
+            }
+            self.skip()?;
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MetaInfo, MetaInfoFileMode};
+    use spate_bencode::Value;
+
+    #[tokio::test]
+    async fn extract_matches_full_parse() {
+        let data = include_bytes!("../../spate/resources/ubuntu-23.10.1-desktop-amd64.iso.torrent");
+        let value = Value::decode(&mut &data[..]).await.unwrap();
+        let meta = MetaInfo::try_from(&value).unwrap();
+        let MetaInfoFileMode::SingleFile(file) = meta.info.files else {
+            panic!("expected a single file torrent");
+        };
+        assert_eq!(extract_torrent_name(data).unwrap(), file.file_name);
+    }
+
+    #[test]
+    fn extract_skips_preceding_values() {
+        let data = b"d8:announce3:url4:infod5:filesld6:lengthi1e4:pathl1:aeee\
+                     6:pieces20:aaaaaaaaaaaaaaaaaaaa4:name9:directoryee";
+        assert_eq!(extract_torrent_name(data).unwrap(), "directory");
+    }
+
+    #[test]
+    fn info_dict_bytes_span() {
+        let data = b"d8:announce3:url4:infod4:name1:a6:lengthi1ee3:zzzi0ee";
+        assert_eq!(info_dict_bytes(data).unwrap(), b"d4:name1:a6:lengthi1ee");
+        assert!(info_dict_bytes(b"d8:announce3:urle").is_err());
+        assert!(info_dict_bytes(b"d4:infod4:name").is_err());
+    }
+
+    #[test]
+    fn extract_missing_name() {
+        assert!(extract_torrent_name(b"d4:infod6:lengthi1eee").is_err());
+        assert!(extract_torrent_name(b"d8:announce3:urle").is_err());
+        assert!(extract_torrent_name(b"d4:infod4:name").is_err());
+        assert!(extract_torrent_name(b"d4:infod4:name99:shorteee").is_err());
+        assert!(extract_torrent_name(b"l4:infoe").is_err());
+    }
+}
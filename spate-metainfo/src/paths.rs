@@ -0,0 +1,140 @@
+use crate::MetaInfo;
+use std::{
+    fs,
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+#[derive(Debug)]
+pub enum PathTraversalError {
+    // The path has a `..`, root or prefix component, or no file name at all.
+    InvalidComponent(PathBuf),
+    // Following symlinks on disk leads outside the root directory.
+    OutsideRoot { path: PathBuf, resolved: PathBuf },
+    IO(PathBuf, io::Error),
+}
+
+impl std::fmt::Display for PathTraversalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidComponent(path) => write!(f, "Invalid path: {}", path.display()),
+            Self::OutsideRoot { path, resolved } => write!(
+                f,
+                "{} resolves to {}, outside the download directory",
+                path.display(),
+                resolved.display()
+            ),
+            Self::IO(path, err) => write!(f, "{}: {}", path.display(), err),
+        }
+    }
+}
+
+impl std::error::Error for PathTraversalError {}
+
+impl MetaInfo {
+    // Creates the parent directories of every file under `root`, checking each one as it
+    // goes so that nothing is created through a symlink that points elsewhere.
+    pub fn validate_file_paths(&self, root: &Path) -> Result<(), Vec<PathTraversalError>> {
+        let root = root
+            .canonicalize()
+            .map_err(|e| vec![PathTraversalError::IO(root.to_path_buf(), e)])?;
+        let errors: Vec<_> = self
+            .files()
+            .into_iter()
+            .filter_map(|file| validate_path(&root, &file.path).err())
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn validate_path(root: &Path, path: &Path) -> Result<(), PathTraversalError> {
+    let components = path
+        .components()
+        .map(|c| match c {
+            Component::Normal(name) => Ok(name),
+            _ => Err(PathTraversalError::InvalidComponent(path.to_path_buf())),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let Some((file_name, parents)) = components.split_last() else {
+        return Err(PathTraversalError::InvalidComponent(path.to_path_buf()));
+    };
+    let io_error = |e| PathTraversalError::IO(path.to_path_buf(), e);
+    let mut resolved = root.to_path_buf();
+    for (i, name) in parents.iter().chain([file_name]).enumerate() {
+        resolved.push(name);
+        match fs::symlink_metadata(&resolved) {
+            Ok(_) => resolved = resolved.canonicalize().map_err(io_error)?,
+            // The file itself doesn't need to exist yet.
+            Err(e) if e.kind() == io::ErrorKind::NotFound && i == parents.len() => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                fs::create_dir(&resolved).map_err(io_error)?
+            }
+            Err(e) => return Err(io_error(e)),
+        }
+        if !resolved.starts_with(root) {
+            return Err(PathTraversalError::OutsideRoot {
+                path: path.to_path_buf(),
+                resolved,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{meta, multi_file_info};
+
+    fn multi_file_torrent(paths: &[&[&str]]) -> MetaInfo {
+        let files: Vec<_> = paths.iter().map(|path| (*path, 16384)).collect();
+        meta(multi_file_info("torrent", 16384, &files, 20 * paths.len()))
+    }
+
+    #[test]
+    fn valid_paths_create_directories() {
+        let root = tempfile::tempdir().unwrap();
+        let meta = multi_file_torrent(&[&["a", "b", "file"], &["c"]]);
+        meta.validate_file_paths(root.path()).unwrap();
+        assert!(root.path().join("torrent/a/b").is_dir());
+        assert!(!root.path().join("torrent/a/b/file").exists());
+        assert!(!root.path().join("torrent/c").exists());
+    }
+
+    #[test]
+    fn parent_components_rejected() {
+        let root = tempfile::tempdir().unwrap();
+        let meta = multi_file_torrent(&[&["..", "..", "escape"], &["ok"]]);
+        let errors = meta.validate_file_paths(root.path()).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0], PathTraversalError::InvalidComponent(_)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_outside_root_flagged() {
+        let root = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let torrent_dir = root.path().join("torrent");
+        fs::create_dir(&torrent_dir).unwrap();
+        std::os::unix::fs::symlink(outside.path(), torrent_dir.join("dir")).unwrap();
+        std::os::unix::fs::symlink(outside.path().join("f"), torrent_dir.join("file")).unwrap();
+        fs::write(outside.path().join("f"), b"").unwrap();
+
+        let meta = multi_file_torrent(&[&["dir", "sub", "a"], &["file"], &["fine"]]);
+        let errors = meta.validate_file_paths(root.path()).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        for err in &errors {
+            let PathTraversalError::OutsideRoot { resolved, .. } = err else {
+                panic!("unexpected error {}", err);
+            };
+            assert!(resolved.starts_with(outside.path().canonicalize().unwrap()));
+        }
+        // Nothing was created through the symlink.
+        assert!(!outside.path().join("sub").exists());
+    }
+}
@@ -0,0 +1,258 @@
+use crate::{hash::hex, MetaInfoFiles, PIECE_HASH_LENGTH};
+use sha1::{Digest, Sha1};
+use std::io::{self, Read};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PieceVerificationError {
+    HashMismatch {
+        piece_index: u32,
+        expected: [u8; 20],
+        actual: [u8; 20],
+    },
+    // The index came from a peer and is past the end of the torrent.
+    InvalidPieceIndex {
+        piece_index: u32,
+        piece_count: usize,
+    },
+}
+
+impl PieceVerificationError {
+    pub fn piece_index(&self) -> u32 {
+        match self {
+            Self::HashMismatch { piece_index, .. }
+            | Self::InvalidPieceIndex { piece_index, .. } => *piece_index,
+        }
+    }
+}
+
+impl std::fmt::Display for PieceVerificationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::HashMismatch {
+                piece_index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Piece {} failed verification: expected {}, got {}",
+                piece_index,
+                hex(expected),
+                hex(actual)
+            ),
+            Self::InvalidPieceIndex {
+                piece_index,
+                piece_count,
+            } => write!(
+                f,
+                "Invalid piece index {}, the torrent has {} pieces",
+                piece_index, piece_count
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PieceVerificationError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidPieceLength(pub i32);
+
+impl std::fmt::Display for InvalidPieceLength {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid piece length {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidPieceLength {}
+
+pub fn verify_piece(
+    data: &[u8],
+    expected: &[u8; 20],
+    piece_index: u32,
+) -> Result<(), PieceVerificationError> {
+    let actual: [u8; 20] = Sha1::digest(data).into();
+    if actual == *expected {
+        return Ok(());
+    }
+    let err = PieceVerificationError::HashMismatch {
+        piece_index,
+        expected: *expected,
+        actual,
+    };
+    log::warn!("{}", err);
+    Err(err)
+}
+
+pub struct PieceVerifier<'a> {
+    info: &'a MetaInfoFiles,
+    piece_length: usize,
+}
+
+impl<'a> PieceVerifier<'a> {
+    pub fn new(info: &'a MetaInfoFiles) -> Result<Self, InvalidPieceLength> {
+        let piece_length = usize::try_from(info.piece_length)
+            .ok()
+            .filter(|&length| length > 0)
+            .ok_or(InvalidPieceLength(info.piece_length))?;
+        Ok(Self { info, piece_length })
+    }
+
+    pub fn piece_count(&self) -> usize {
+        self.info.pieces.len() / PIECE_HASH_LENGTH
+    }
+
+    // Checks a single piece as it comes in.
+    pub fn verify(&self, piece_index: u32, data: &[u8]) -> Result<(), PieceVerificationError> {
+        let expected = (piece_index as usize)
+            .checked_mul(PIECE_HASH_LENGTH)
+            .and_then(|start| self.info.pieces.get(start..start + PIECE_HASH_LENGTH))
+            .ok_or(PieceVerificationError::InvalidPieceIndex {
+                piece_index,
+                piece_count: self.piece_count(),
+            })?;
+        verify_piece(data, expected.try_into().unwrap(), piece_index)
+    }
+
+    // Checks every piece of `data`, the torrent's files concatenated in order. A short read
+    // fails the pieces it cuts off rather than being reported separately.
+    pub fn verify_all(&self, data: &[u8]) -> Vec<PieceVerificationError> {
+        (0..self.piece_count())
+            .filter_map(|i| {
+                let start = (i * self.piece_length).min(data.len());
+                let end = (start + self.piece_length).min(data.len());
+                self.verify(i as u32, &data[start..end]).err()
+            })
+            .collect()
+    }
+
+    // Same as verify_all, but only keeps one piece in memory at a time.
+    pub fn verify_reader<R: Read>(
+        &self,
+        mut reader: R,
+    ) -> Result<Vec<PieceVerificationError>, io::Error> {
+        let mut buf = Vec::with_capacity(self.piece_length);
+        let mut errors = Vec::new();
+        for i in 0..self.piece_count() {
+            buf.clear();
+            reader
+                .by_ref()
+                .take(self.piece_length as u64)
+                .read_to_end(&mut buf)?;
+            if let Err(err) = self.verify(i as u32, &buf) {
+                errors.push(err);
+            }
+        }
+        Ok(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MetaInfoFileMode, MetaInfoSingleFile};
+
+    const PIECE_LENGTH: usize = 16 * 1024;
+
+    fn sha1(data: &[u8]) -> [u8; 20] {
+        Sha1::digest(data).into()
+    }
+
+    fn info(data: &[u8]) -> MetaInfoFiles {
+        MetaInfoFiles {
+            piece_length: PIECE_LENGTH as i32,
+            pieces: data.chunks(PIECE_LENGTH).flat_map(sha1).collect(),
+            private: false,
+            files: MetaInfoFileMode::SingleFile(MetaInfoSingleFile {
+                file_name: String::from("file"),
+                length: data.len(),
+                md5sum: None,
+            }),
+            meta_version: None,
+        }
+    }
+
+    #[test]
+    fn verify_piece_reports_hashes() {
+        let data = vec![7; PIECE_LENGTH];
+        let expected = sha1(&data);
+        assert_eq!(verify_piece(&data, &expected, 3), Ok(()));
+
+        let mut corrupt = data.clone();
+        corrupt[100] ^= 0xff;
+        let err = verify_piece(&corrupt, &expected, 3).unwrap_err();
+        assert_eq!(
+            err,
+            PieceVerificationError::HashMismatch {
+                piece_index: 3,
+                expected,
+                actual: sha1(&corrupt),
+            }
+        );
+        assert!(err.to_string().contains(&hex(&expected)));
+    }
+
+    #[test]
+    fn verify_all_finds_corrupt_pieces() {
+        let mut data: Vec<u8> = (0..PIECE_LENGTH * 3 + 10).map(|i| i as u8).collect();
+        let info = info(&data);
+        let verifier = PieceVerifier::new(&info).unwrap();
+        assert!(verifier.verify_all(&data).is_empty());
+
+        data[PIECE_LENGTH + 1] ^= 0xff;
+        let errors = verifier.verify_all(&data);
+        assert_eq!(
+            errors,
+            vec![PieceVerificationError::HashMismatch {
+                piece_index: 1,
+                expected: info.pieces[20..40].try_into().unwrap(),
+                actual: sha1(&data[PIECE_LENGTH..2 * PIECE_LENGTH]),
+            }]
+        );
+
+        let errors = verifier.verify_all(&data[..PIECE_LENGTH * 2]);
+        let failed: Vec<_> = errors.iter().map(|e| e.piece_index()).collect();
+        assert_eq!(failed, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn verify_single_pieces_and_readers() {
+        let mut data: Vec<u8> = (0..PIECE_LENGTH * 3 + 10).map(|i| i as u8).collect();
+        let info = info(&data);
+        let verifier = PieceVerifier::new(&info).unwrap();
+        assert_eq!(verifier.piece_count(), 4);
+        assert_eq!(verifier.verify(3, &data[PIECE_LENGTH * 3..]), Ok(()));
+        assert!(verifier.verify(2, &data[PIECE_LENGTH * 3..]).is_err());
+
+        data[10] ^= 0xff;
+        let errors = verifier.verify_reader(&data[..PIECE_LENGTH * 2]).unwrap();
+        let failed: Vec<_> = errors.iter().map(|e| e.piece_index()).collect();
+        assert_eq!(failed, vec![0, 2, 3]);
+    }
+
+    #[test]
+    fn verify_rejects_out_of_range_index() {
+        let data = vec![0; PIECE_LENGTH * 2];
+        let info = info(&data);
+        let verifier = PieceVerifier::new(&info).unwrap();
+        for piece_index in [verifier.piece_count() as u32, u32::MAX] {
+            assert_eq!(
+                verifier.verify(piece_index, &data[..PIECE_LENGTH]),
+                Err(PieceVerificationError::InvalidPieceIndex {
+                    piece_index,
+                    piece_count: 2,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_piece_length() {
+        let mut info = info(&[0; 10]);
+        info.piece_length = -1;
+        assert_eq!(
+            PieceVerifier::new(&info).err(),
+            Some(InvalidPieceLength(-1))
+        );
+        info.piece_length = 0;
+        assert!(PieceVerifier::new(&info).is_err());
+    }
+}
@@ -0,0 +1,54 @@
+use crate::MetaInfo;
+use spate_bencode::Value;
+use std::collections::BTreeMap;
+
+// `pieces` is the length of the pieces string rather than a piece count, so that tests can
+// make it disagree with the file lengths.
+pub(crate) fn single_file_info(name: &str, piece_length: i64, length: i64, pieces: usize) -> Value {
+    Value::Dict(BTreeMap::from([
+        (Value::from("name"), Value::from(name)),
+        (Value::from("piece length"), Value::Integer(piece_length)),
+        (Value::from("pieces"), Value::Bytes(vec![0; pieces])),
+        (Value::from("length"), Value::Integer(length)),
+    ]))
+}
+
+pub(crate) fn multi_file_info(
+    name: &str,
+    piece_length: i64,
+    files: &[(&[&str], i64)],
+    pieces: usize,
+) -> Value {
+    let files = files
+        .iter()
+        .map(|(path, length)| {
+            Value::Dict(BTreeMap::from([
+                (Value::from("length"), Value::Integer(*length)),
+                (
+                    Value::from("path"),
+                    Value::List(path.iter().map(|p| Value::from(*p)).collect()),
+                ),
+            ]))
+        })
+        .collect();
+    Value::Dict(BTreeMap::from([
+        (Value::from("name"), Value::from(name)),
+        (Value::from("piece length"), Value::Integer(piece_length)),
+        (Value::from("pieces"), Value::Bytes(vec![0; pieces])),
+        (Value::from("files"), Value::List(files)),
+    ]))
+}
+
+pub(crate) fn torrent(info: Value) -> Value {
+    Value::Dict(BTreeMap::from([
+        (
+            Value::from("announce"),
+            Value::from("http://tracker/announce"),
+        ),
+        (Value::from("info"), info),
+    ]))
+}
+
+pub(crate) fn meta(info: Value) -> MetaInfo {
+    MetaInfo::try_from(&torrent(info)).unwrap()
+}
@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::fs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BanReason {
+    // The peer sent piece data that failed hash verification.
+    CorruptData,
+    // The peer broke the wire protocol in a way that is probably a bug rather than malice.
+    ProtocolViolation,
+}
+
+impl BanReason {
+    pub fn duration(&self) -> Duration {
+        match self {
+            Self::CorruptData => Duration::from_secs(7 * 24 * 60 * 60),
+            Self::ProtocolViolation => Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+impl std::fmt::Display for BanReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CorruptData => write!(f, "corrupt data"),
+            Self::ProtocolViolation => write!(f, "protocol violation"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlacklistEntry {
+    pub addr: Option<IpAddr>,
+    pub peer_id: Option<[u8; 20]>,
+    pub reason: String,
+    // Unix timestamp in seconds after which the entry no longer applies
+    pub expires: u64,
+}
+
+impl BlacklistEntry {
+    fn matches(&self, addr: &IpAddr, peer_id: Option<&[u8; 20]>) -> bool {
+        self.addr.as_ref() == Some(addr)
+            || (self.peer_id.is_some() && self.peer_id.as_ref() == peer_id)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerBlacklist {
+    pub entries: Vec<BlacklistEntry>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+impl PeerBlacklist {
+    // `~/.config/spate/blacklist.json`, or None when the home directory is unknown.
+    pub fn default_path() -> Option<PathBuf> {
+        let home = std::env::var_os("HOME")?;
+        Some(PathBuf::from(home).join(".config/spate/blacklist.json"))
+    }
+
+    // A missing file is treated as an empty blacklist.
+    pub async fn load(path: &Path) -> Result<Self, io::Error> {
+        match fs::read(path).await {
+            Ok(data) => serde_json::from_slice(&data).map_err(io::Error::from),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<(), io::Error> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let live = Self {
+            entries: self
+                .entries
+                .iter()
+                .filter(|e| e.expires > now())
+                .cloned()
+                .collect(),
+        };
+        fs::write(path, serde_json::to_vec_pretty(&live)?).await
+    }
+
+    pub fn ban(&mut self, addr: Option<IpAddr>, peer_id: Option<[u8; 20]>, reason: BanReason) {
+        self.entries.push(BlacklistEntry {
+            addr,
+            peer_id,
+            reason: reason.to_string(),
+            expires: now() + reason.duration().as_secs(),
+        });
+    }
+
+    pub fn is_banned(&self, addr: &IpAddr, peer_id: Option<&[u8; 20]>) -> bool {
+        self.is_banned_at(addr, peer_id, now())
+    }
+
+    pub fn is_banned_at(&self, addr: &IpAddr, peer_id: Option<&[u8; 20]>, now: u64) -> bool {
+        self.entries
+            .iter()
+            .any(|e| e.expires > now && e.matches(addr, peer_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    #[tokio::test]
+    async fn save_and_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("spate/blacklist.json");
+        let addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+        let mut blacklist = PeerBlacklist::default();
+        blacklist.ban(Some(addr), None, BanReason::CorruptData);
+        blacklist.save(&path).await.unwrap();
+
+        let loaded = PeerBlacklist::load(&path).await.unwrap();
+        assert_eq!(loaded, blacklist);
+        assert_eq!(loaded.entries[0].reason, "corrupt data");
+        assert!(loaded.is_banned(&addr, None));
+        assert!(loaded.is_banned_at(&addr, None, now() + 6 * DAY));
+        assert!(!loaded.is_banned_at(&addr, None, now() + 8 * DAY));
+    }
+
+    #[tokio::test]
+    async fn load_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let loaded = PeerBlacklist::load(&dir.path().join("missing.json"))
+            .await
+            .unwrap();
+        assert!(loaded.entries.is_empty());
+    }
+
+    #[tokio::test]
+    async fn save_drops_expired_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blacklist.json");
+        let blacklist = PeerBlacklist {
+            entries: vec![BlacklistEntry {
+                addr: Some(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+                peer_id: None,
+                reason: String::from("protocol violation"),
+                expires: 1,
+            }],
+        };
+        blacklist.save(&path).await.unwrap();
+        assert!(PeerBlacklist::load(&path).await.unwrap().entries.is_empty());
+    }
+
+    #[test]
+    fn ban_by_peer_id() {
+        let peer_id = *b"-qB4530-abcdefghijkl";
+        let addr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let mut blacklist = PeerBlacklist::default();
+        blacklist.ban(None, Some(peer_id), BanReason::ProtocolViolation);
+
+        assert!(blacklist.is_banned(&addr, Some(&peer_id)));
+        assert!(!blacklist.is_banned(&addr, Some(&[0; 20])));
+        assert!(!blacklist.is_banned(&addr, None));
+        assert!(blacklist.is_banned_at(&addr, Some(&peer_id), now() + DAY - 60));
+        assert!(!blacklist.is_banned_at(&addr, Some(&peer_id), now() + DAY + 60));
+    }
+}
@@ -0,0 +1,51 @@
+use std::{collections::HashMap, time::Duration};
+use tokio::time::Instant;
+
+const CANCEL_WINDOW: Duration = Duration::from_secs(30);
+
+// Cancels recently sent to a single peer, so endgame mode doesn't repeat them.
+#[derive(Debug, Default)]
+pub struct CancelSet {
+    sent: HashMap<(u32, u32, u32), Instant>,
+}
+
+impl CancelSet {
+    // Records the cancel when it returns true, so the caller must then send it.
+    pub fn should_send_cancel(&mut self, piece: u32, begin: u32, length: u32) -> bool {
+        let now = Instant::now();
+        self.sent
+            .retain(|_, sent| now.duration_since(*sent) < CANCEL_WINDOW);
+        if self.sent.contains_key(&(piece, begin, length)) {
+            return false;
+        }
+        self.sent.insert((piece, begin, length), now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn suppresses_duplicate_cancels() {
+        let mut cancels = CancelSet::default();
+        assert!(cancels.should_send_cancel(1, 0, 16384));
+        assert!(!cancels.should_send_cancel(1, 0, 16384));
+        assert!(cancels.should_send_cancel(1, 16384, 16384));
+        assert!(cancels.should_send_cancel(1, 0, 8192));
+        assert!(cancels.should_send_cancel(2, 0, 16384));
+
+        tokio::time::advance(Duration::from_secs(29)).await;
+        assert!(!cancels.should_send_cancel(1, 0, 16384));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancel_window_expires() {
+        let mut cancels = CancelSet::default();
+        assert!(cancels.should_send_cancel(1, 0, 16384));
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert!(cancels.should_send_cancel(1, 0, 16384));
+        assert_eq!(cancels.sent.len(), 1);
+    }
+}
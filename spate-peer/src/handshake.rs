@@ -0,0 +1,173 @@
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+pub const PROTOCOL: &[u8; 19] = b"BitTorrent protocol";
+pub const HANDSHAKE_LENGTH: usize = 1 + PROTOCOL.len() + 8 + 20 + 20;
+
+// Azureus-style client codes, see https://wiki.theory.org/BitTorrentSpecification#peer_id
+const KNOWN_CLIENTS: &[(&[u8; 2], &str)] = &[
+    (b"AZ", "Vuze"),
+    (b"BC", "BitComet"),
+    (b"BI", "BiglyBT"),
+    (b"BT", "BitTorrent"),
+    (b"DE", "Deluge"),
+    (b"FW", "FrostWire"),
+    (b"KT", "KTorrent"),
+    (b"LT", "libtorrent"),
+    (b"lt", "rTorrent"),
+    (b"qB", "qBittorrent"),
+    (b"SD", "Thunder"),
+    (b"TR", "Transmission"),
+    (b"UM", "µTorrent Mac"),
+    (b"UT", "µTorrent"),
+    (b"UW", "µTorrent Web"),
+    (b"WW", "WebTorrent"),
+    (b"XL", "Xunlei"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Handshake {
+    pub reserved: [u8; 8],
+    pub info_hash: [u8; 20],
+    pub peer_id: [u8; 20],
+}
+
+impl Handshake {
+    pub fn new(info_hash: [u8; 20], peer_id: [u8; 20]) -> Self {
+        Self {
+            reserved: [0; 8],
+            info_hash,
+            peer_id,
+        }
+    }
+
+    pub async fn read<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Self, io::Error> {
+        let mut buf = [0; HANDSHAKE_LENGTH];
+        reader.read_exact(&mut buf).await?;
+        if buf[0] as usize != PROTOCOL.len() || &buf[1..20] != PROTOCOL {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unknown handshake protocol",
+            ));
+        }
+        let mut handshake = Self::new([0; 20], [0; 20]);
+        handshake.reserved.copy_from_slice(&buf[20..28]);
+        handshake.info_hash.copy_from_slice(&buf[28..48]);
+        handshake.peer_id.copy_from_slice(&buf[48..68]);
+        Ok(handshake)
+    }
+
+    pub async fn write<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> Result<(), io::Error> {
+        let mut buf = Vec::with_capacity(HANDSHAKE_LENGTH);
+        buf.push(PROTOCOL.len() as u8);
+        buf.extend_from_slice(PROTOCOL);
+        buf.extend_from_slice(&self.reserved);
+        buf.extend_from_slice(&self.info_hash);
+        buf.extend_from_slice(&self.peer_id);
+        writer.write_all(&buf).await
+    }
+
+    pub fn peer_client_name(&self) -> Option<String> {
+        let (code, _) = self.azureus_prefix()?;
+        let (_, name) = KNOWN_CLIENTS.iter().find(|(c, _)| *c == code)?;
+        Some(format!("{} {}", name, self.peer_client_version()?))
+    }
+
+    pub fn peer_client_version(&self) -> Option<String> {
+        let (_, version) = self.azureus_prefix()?;
+        let mut digits = version
+            .iter()
+            .map(|c| match c {
+                b'0'..=b'9' => Some(c - b'0'),
+                b'A'..=b'Z' => Some(c - b'A' + 10),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        // Major and minor are always shown, trailing zero components are not.
+        while digits.len() > 2 && digits.last() == Some(&0) {
+            digits.pop();
+        }
+        Some(
+            digits
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>()
+                .join("."),
+        )
+    }
+
+    // Splits a `-XY1234-` peer ID into its client code and version characters.
+    fn azureus_prefix(&self) -> Option<(&[u8; 2], &[u8])> {
+        let id = &self.peer_id;
+        if id[0] != b'-' || id[7] != b'-' {
+            return None;
+        }
+        Some((id[1..3].try_into().ok()?, &id[3..7]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handshake(prefix: &[u8]) -> Handshake {
+        let mut peer_id = [b'x'; 20];
+        peer_id[..prefix.len()].copy_from_slice(prefix);
+        Handshake::new([0; 20], peer_id)
+    }
+
+    #[tokio::test]
+    async fn handshake_roundtrip() {
+        let input = Handshake::new([1; 20], *b"-qB4530-abcdefghijkl");
+        let mut buf = Vec::new();
+        input.write(&mut buf).await.unwrap();
+        assert_eq!(buf.len(), HANDSHAKE_LENGTH);
+        assert_eq!(&buf[..20], b"\x13BitTorrent protocol");
+
+        let got = Handshake::read(&mut buf.as_slice()).await.unwrap();
+        assert_eq!(got, input);
+    }
+
+    #[tokio::test]
+    async fn read_rejects_unknown_protocol() {
+        let mut buf = vec![19];
+        buf.extend_from_slice(b"BitTorrent protocal");
+        buf.resize(HANDSHAKE_LENGTH, 0);
+        assert!(Handshake::read(&mut buf.as_slice()).await.is_err());
+    }
+
+    #[test]
+    fn peer_client_names() {
+        let cases: [(&[u8], &str); 10] = [
+            (b"-qB4530-", "qBittorrent 4.5.3"),
+            (b"-TR3000-", "Transmission 3.0"),
+            (b"-TR2940-", "Transmission 2.9.4"),
+            (b"-UT3550-", "µTorrent 3.5.5"),
+            (b"-DE13F0-", "Deluge 1.3.15"),
+            (b"-LT1200-", "libtorrent 1.2"),
+            (b"-lt0D80-", "rTorrent 0.13.8"),
+            (b"-AZ5770-", "Vuze 5.7.7"),
+            (b"-BI3200-", "BiglyBT 3.2"),
+            (b"-WW0105-", "WebTorrent 0.1.0.5"),
+        ];
+        for (prefix, name) in cases {
+            assert_eq!(handshake(prefix).peer_client_name().as_deref(), Some(name));
+        }
+        assert_eq!(
+            handshake(b"-qB4530-").peer_client_version().as_deref(),
+            Some("4.5.3")
+        );
+    }
+
+    #[test]
+    fn peer_client_name_unrecognized() {
+        assert_eq!(handshake(b"-ZZ1000-").peer_client_name(), None);
+        assert_eq!(
+            handshake(b"-ZZ1000-").peer_client_version().as_deref(),
+            Some("1.0")
+        );
+        assert_eq!(handshake(b"S58B-----").peer_client_name(), None);
+        assert_eq!(handshake(b"M7-4-3--").peer_client_name(), None);
+        assert_eq!(handshake(b"-qB4.5.-").peer_client_name(), None);
+    }
+}
@@ -0,0 +1,13 @@
+mod blacklist;
+mod cancel;
+mod handshake;
+mod lsd;
+mod pipeline;
+mod upload;
+
+pub use blacklist::*;
+pub use cancel::*;
+pub use handshake::*;
+pub use lsd::*;
+pub use pipeline::*;
+pub use upload::*;
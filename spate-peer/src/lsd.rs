@@ -0,0 +1,342 @@
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    collections::HashMap,
+    io,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    net::UdpSocket,
+    sync::mpsc::{self, error::TrySendError},
+    task::JoinHandle,
+    time::Instant,
+};
+
+// BEP-14 Local Service Discovery, see https://www.bittorrent.org/beps/bep_0014.html
+pub const LSD_MULTICAST_GROUP: SocketAddrV4 =
+    SocketAddrV4::new(Ipv4Addr::new(239, 192, 152, 143), 6771);
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const MULTICAST_TTL: u32 = 4;
+const MAX_PACKET_SIZE: usize = 1400;
+// Peers found while the channel is full are dropped, they announce again later.
+const PEER_CHANNEL_CAPACITY: usize = 64;
+// Repeated announces of the same peer within this window are only reported once.
+const DEDUP_WINDOW: Duration = Duration::from_secs(60);
+const RECV_ERROR_BACKOFF: Duration = Duration::from_secs(1);
+
+// Info hash to the port we accept peer connections on for that torrent.
+pub type LsdTorrents = Arc<Mutex<HashMap<[u8; 20], u16>>>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LsdAnnounce {
+    pub port: u16,
+    pub info_hashes: Vec<[u8; 20]>,
+    // Lets a client recognise its own announces when they are looped back.
+    pub cookie: Option<String>,
+}
+
+impl LsdAnnounce {
+    pub fn to_bytes(&self, group: SocketAddrV4) -> Vec<u8> {
+        let mut message = format!(
+            "BT-SEARCH * HTTP/1.1\r\nHost: {}\r\nPort: {}\r\n",
+            group, self.port
+        );
+        for info_hash in &self.info_hashes {
+            let hex: String = info_hash.iter().map(|b| format!("{:02x}", b)).collect();
+            message.push_str(&format!("Infohash: {}\r\n", hex));
+        }
+        if let Some(cookie) = &self.cookie {
+            message.push_str(&format!("cookie: {}\r\n", cookie));
+        }
+        message.push_str("\r\n\r\n");
+        message.into_bytes()
+    }
+
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let message = std::str::from_utf8(data).ok()?;
+        let mut lines = message.split("\r\n");
+        if lines.next()? != "BT-SEARCH * HTTP/1.1" {
+            return None;
+        }
+        let mut announce = Self {
+            port: 0,
+            info_hashes: Vec::new(),
+            cookie: None,
+        };
+        for line in lines.take_while(|l| !l.is_empty()) {
+            // Unknown or malformed headers are ignored rather than rejecting the announce.
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match name.to_ascii_lowercase().as_str() {
+                "port" => announce.port = value.parse().ok()?,
+                "infohash" => announce.info_hashes.push(parse_info_hash(value)?),
+                "cookie" => announce.cookie = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        if announce.port == 0 || announce.info_hashes.is_empty() {
+            return None;
+        }
+        Some(announce)
+    }
+}
+
+fn parse_info_hash(hex: &str) -> Option<[u8; 20]> {
+    if hex.len() != 40 || !hex.is_ascii() {
+        return None;
+    }
+    let mut info_hash = [0; 20];
+    for (i, b) in info_hash.iter_mut().enumerate() {
+        *b = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok()?;
+    }
+    Some(info_hash)
+}
+
+// Announces our torrents to the LAN and reports peers that announce the same ones. The
+// background tasks stop when this is dropped.
+pub struct LocalPeerDiscovery {
+    peers: mpsc::Receiver<([u8; 20], SocketAddr)>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl LocalPeerDiscovery {
+    pub fn new(torrents: LsdTorrents) -> Result<Self, io::Error> {
+        Self::with_group(torrents, LSD_MULTICAST_GROUP)
+    }
+
+    pub fn with_group(torrents: LsdTorrents, group: SocketAddrV4) -> Result<Self, io::Error> {
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_reuse_address(true)?;
+        socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, group.port()).into())?;
+        socket.join_multicast_v4(group.ip(), &Ipv4Addr::UNSPECIFIED)?;
+        socket.set_multicast_ttl_v4(MULTICAST_TTL)?;
+        socket.set_multicast_loop_v4(true)?;
+        socket.set_nonblocking(true)?;
+        let socket = Arc::new(UdpSocket::from_std(socket.into())?);
+
+        let cookie = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| format!("{:x}{:x}", std::process::id(), d.subsec_nanos()))
+            .unwrap_or_default();
+        let (tx, peers) = mpsc::channel(PEER_CHANNEL_CAPACITY);
+        let tasks = vec![
+            tokio::spawn(announce(
+                socket.clone(),
+                torrents.clone(),
+                group,
+                cookie.clone(),
+            )),
+            tokio::spawn(listen(socket, torrents, cookie, tx)),
+        ];
+        Ok(Self { peers, tasks })
+    }
+
+    // The next peer announcing one of our torrents, as (info hash, peer address).
+    pub async fn next_peer(&mut self) -> Option<([u8; 20], SocketAddr)> {
+        self.peers.recv().await
+    }
+}
+
+impl Drop for LocalPeerDiscovery {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+async fn announce(
+    socket: Arc<UdpSocket>,
+    torrents: LsdTorrents,
+    group: SocketAddrV4,
+    cookie: String,
+) {
+    let mut interval = tokio::time::interval(ANNOUNCE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let messages: Vec<_> = torrents
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(info_hash, port)| {
+                LsdAnnounce {
+                    port: *port,
+                    info_hashes: vec![*info_hash],
+                    cookie: Some(cookie.clone()),
+                }
+                .to_bytes(group)
+            })
+            .collect();
+        for message in messages {
+            // A failed announce is retried on the next tick.
+            let _ = socket.send_to(&message, group).await;
+        }
+    }
+}
+
+async fn listen(
+    socket: Arc<UdpSocket>,
+    torrents: LsdTorrents,
+    cookie: String,
+    peers: mpsc::Sender<([u8; 20], SocketAddr)>,
+) {
+    let mut buf = [0; MAX_PACKET_SIZE];
+    let mut dedup = PeerDedup::new(DEDUP_WINDOW);
+    loop {
+        let (len, from) = match socket.recv_from(&mut buf).await {
+            Ok(received) => received,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+                ) =>
+            {
+                continue
+            }
+            Err(e) => {
+                // E.g. the interface went down, retrying straight away would only spin.
+                log::warn!("LSD receive failed: {}", e);
+                tokio::time::sleep(RECV_ERROR_BACKOFF).await;
+                continue;
+            }
+        };
+        let Some(announce) = LsdAnnounce::parse(&buf[..len]) else {
+            continue;
+        };
+        if announce.cookie.as_ref() == Some(&cookie) {
+            continue;
+        }
+        let found: Vec<_> = {
+            let torrents = torrents.lock().unwrap();
+            announce
+                .info_hashes
+                .into_iter()
+                .filter(|info_hash| torrents.contains_key(info_hash))
+                .collect()
+        };
+        let addr = SocketAddr::new(from.ip(), announce.port);
+        for info_hash in found {
+            if !dedup.is_new(info_hash, addr, Instant::now()) {
+                continue;
+            }
+            if let Err(TrySendError::Closed(_)) = peers.try_send((info_hash, addr)) {
+                return;
+            }
+        }
+    }
+}
+
+// Remembers recently reported (info hash, peer) pairs.
+struct PeerDedup {
+    window: Duration,
+    seen: HashMap<([u8; 20], SocketAddr), Instant>,
+}
+
+impl PeerDedup {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            seen: HashMap::new(),
+        }
+    }
+
+    fn is_new(&mut self, info_hash: [u8; 20], addr: SocketAddr, now: Instant) -> bool {
+        self.seen
+            .retain(|_, seen| now.duration_since(*seen) < self.window);
+        if self.seen.contains_key(&(info_hash, addr)) {
+            return false;
+        }
+        self.seen.insert((info_hash, addr), now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INFO_HASH: [u8; 20] = [0xab; 20];
+
+    #[test]
+    fn announce_roundtrip() {
+        let announce = LsdAnnounce {
+            port: 6881,
+            info_hashes: vec![INFO_HASH, [0; 20]],
+            cookie: Some(String::from("spate")),
+        };
+        let bytes = announce.to_bytes(LSD_MULTICAST_GROUP);
+        assert!(bytes.starts_with(b"BT-SEARCH * HTTP/1.1\r\nHost: 239.192.152.143:6771\r\n"));
+        assert_eq!(LsdAnnounce::parse(&bytes), Some(announce));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_announces() {
+        assert_eq!(LsdAnnounce::parse(b"GET / HTTP/1.1\r\n\r\n"), None);
+        assert_eq!(
+            LsdAnnounce::parse(b"BT-SEARCH * HTTP/1.1\r\nPort: 1\r\n\r\n"),
+            None
+        );
+        assert_eq!(
+            LsdAnnounce::parse(b"BT-SEARCH * HTTP/1.1\r\nPort: 1\r\nInfohash: abc\r\n\r\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_skips_malformed_headers() {
+        let announce = LsdAnnounce::parse(
+            format!(
+                "BT-SEARCH * HTTP/1.1\r\nPort: 1\r\njunk\r\nInfohash: {}\r\n\r\n",
+                "ab".repeat(20)
+            )
+            .as_bytes(),
+        );
+        assert_eq!(
+            announce,
+            Some(LsdAnnounce {
+                port: 1,
+                info_hashes: vec![INFO_HASH],
+                cookie: None,
+            })
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dedup_within_window() {
+        let mut dedup = PeerDedup::new(DEDUP_WINDOW);
+        let addr: SocketAddr = "127.0.0.1:7000".parse().unwrap();
+        assert!(dedup.is_new(INFO_HASH, addr, Instant::now()));
+        assert!(!dedup.is_new(INFO_HASH, addr, Instant::now()));
+        assert!(dedup.is_new([0; 20], addr, Instant::now()));
+
+        tokio::time::advance(DEDUP_WINDOW).await;
+        assert!(dedup.is_new(INFO_HASH, addr, Instant::now()));
+    }
+
+    #[tokio::test]
+    async fn discovers_peer_from_mock_packet() {
+        let group = SocketAddrV4::new(*LSD_MULTICAST_GROUP.ip(), 16771);
+        let torrents = LsdTorrents::default();
+        torrents.lock().unwrap().insert(INFO_HASH, 6881);
+        let mut discovery = LocalPeerDiscovery::with_group(torrents, group).unwrap();
+
+        let mock = LsdAnnounce {
+            port: 7000,
+            info_hashes: vec![[0; 20], INFO_HASH],
+            cookie: Some(String::from("other client")),
+        };
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        sender
+            .send_to(&mock.to_bytes(group), (Ipv4Addr::LOCALHOST, group.port()))
+            .await
+            .unwrap();
+
+        let peer = tokio::time::timeout(Duration::from_secs(5), discovery.next_peer())
+            .await
+            .unwrap();
+        assert_eq!(peer, Some((INFO_HASH, "127.0.0.1:7000".parse().unwrap())));
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashSet;
+
+pub const DEFAULT_PIPELINE_DEPTH: usize = 5;
+
+// Tracks the block requests sent to a single peer that have not been answered yet.
+#[derive(Debug)]
+pub struct PipelineManager {
+    pub max_pending: usize,
+    pending: HashSet<(u32, u32)>,
+}
+
+impl Default for PipelineManager {
+    fn default() -> Self {
+        Self::new(DEFAULT_PIPELINE_DEPTH)
+    }
+}
+
+impl PipelineManager {
+    pub fn new(max_pending: usize) -> Self {
+        Self {
+            max_pending,
+            pending: HashSet::with_capacity(max_pending),
+        }
+    }
+
+    pub fn can_request(&self) -> bool {
+        self.pending.len() < self.max_pending
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn sent_request(&mut self, piece: u32, block: u32) {
+        self.pending.insert((piece, block));
+    }
+
+    // Returns false if the block was never requested, e.g. after a cancel.
+    pub fn received_piece(&mut self, piece: u32, block: u32) -> bool {
+        self.pending.remove(&(piece, block))
+    }
+
+    // Frees the slot of a request we sent a cancel for, returns false if it wasn't pending.
+    pub fn cancel(&mut self, piece: u32, block: u32) -> bool {
+        self.pending.remove(&(piece, block))
+    }
+
+    // Drops every outstanding request, e.g. when the peer chokes us or disconnects.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    #[test]
+    fn default_depth() {
+        assert_eq!(PipelineManager::default().max_pending, 5);
+    }
+
+    #[test]
+    fn high_latency_peer_keeps_pipeline_full() {
+        let mut pipeline = PipelineManager::default();
+        let mut wanted = (0..4).flat_map(|piece| (0..8).map(move |block| (piece, block)));
+        let mut in_flight = VecDeque::new();
+        let mut received = 0;
+
+        loop {
+            while pipeline.can_request() {
+                let Some((piece, block)) = wanted.next() else {
+                    break;
+                };
+                pipeline.sent_request(piece, block);
+                in_flight.push_back((piece, block));
+            }
+            if in_flight.is_empty() {
+                break;
+            }
+            if received < 32 - 5 {
+                assert_eq!(pipeline.pending(), 5);
+            }
+            // The peer answers one request per round trip, oldest first.
+            let (piece, block) = in_flight.pop_front().unwrap();
+            assert!(pipeline.received_piece(piece, block));
+            received += 1;
+        }
+        assert_eq!(received, 32);
+        assert_eq!(pipeline.pending(), 0);
+    }
+
+    #[test]
+    fn cancel_frees_slot() {
+        let mut pipeline = PipelineManager::new(2);
+        pipeline.sent_request(0, 0);
+        pipeline.sent_request(0, 1);
+        assert!(!pipeline.can_request());
+        assert!(pipeline.cancel(0, 1));
+        assert!(!pipeline.cancel(0, 1));
+        assert!(pipeline.can_request());
+        assert!(!pipeline.received_piece(0, 1));
+
+        pipeline.sent_request(1, 0);
+        pipeline.clear();
+        assert_eq!(pipeline.pending(), 0);
+    }
+
+    #[test]
+    fn received_unrequested_block() {
+        let mut pipeline = PipelineManager::new(1);
+        pipeline.sent_request(0, 0);
+        assert!(!pipeline.can_request());
+        assert!(!pipeline.received_piece(0, 1));
+        assert!(!pipeline.can_request());
+        assert!(pipeline.received_piece(0, 0));
+        assert!(pipeline.can_request());
+    }
+}
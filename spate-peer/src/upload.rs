@@ -0,0 +1,133 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+pub const DEFAULT_UPLOAD_SLOTS: usize = 4;
+const RATE_WINDOW: Duration = Duration::from_secs(10);
+
+pub type PeerId = [u8; 20];
+
+// Limits how many peers are unchoked, i.e. allowed to download from us, at once.
+#[derive(Debug)]
+pub struct UploadSlotManager {
+    pub max_slots: usize,
+    active: HashSet<PeerId>,
+}
+
+impl Default for UploadSlotManager {
+    fn default() -> Self {
+        Self::new(DEFAULT_UPLOAD_SLOTS)
+    }
+}
+
+impl UploadSlotManager {
+    pub fn new(max_slots: usize) -> Self {
+        Self {
+            max_slots,
+            active: HashSet::with_capacity(max_slots),
+        }
+    }
+
+    pub fn can_unchoke(&self, peer_id: &PeerId) -> bool {
+        self.active.contains(peer_id) || self.active.len() < self.max_slots
+    }
+
+    // Returns false if every slot is taken, in which case no Unchoke should be sent.
+    pub fn unchoke(&mut self, peer_id: PeerId) -> bool {
+        if !self.can_unchoke(&peer_id) {
+            return false;
+        }
+        self.active.insert(peer_id);
+        true
+    }
+
+    // Frees the peer's slot, also used when the peer disconnects.
+    pub fn choke(&mut self, peer_id: &PeerId) {
+        self.active.remove(peer_id);
+    }
+
+    pub fn unchoked(&self) -> usize {
+        self.active.len()
+    }
+}
+
+// Bytes sent to a peer over the last ten seconds.
+#[derive(Debug, Default)]
+pub struct UploadRate {
+    sent: VecDeque<(Instant, u64)>,
+    total: u64,
+}
+
+impl UploadRate {
+    pub fn record(&mut self, bytes: u64) {
+        let now = Instant::now();
+        self.expire(now);
+        self.sent.push_back((now, bytes));
+        self.total += bytes;
+    }
+
+    pub fn upload_rate_bps(&mut self) -> u64 {
+        self.expire(Instant::now());
+        self.total / RATE_WINDOW.as_secs()
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some((sent, bytes)) = self.sent.front() {
+            if now.duration_since(*sent) < RATE_WINDOW {
+                break;
+            }
+            self.total -= bytes;
+            self.sent.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(i: u8) -> PeerId {
+        [i; 20]
+    }
+
+    #[test]
+    fn only_max_slots_peers_unchoked() {
+        let mut slots = UploadSlotManager::new(3);
+        let unchoked: Vec<_> = (0..5).map(|i| slots.unchoke(peer(i))).collect();
+        assert_eq!(unchoked, vec![true, true, true, false, false]);
+        assert_eq!(slots.unchoked(), 3);
+        assert!(slots.can_unchoke(&peer(0)));
+        assert!(!slots.can_unchoke(&peer(4)));
+
+        slots.choke(&peer(1));
+        assert!(slots.unchoke(peer(4)));
+        assert!(!slots.unchoke(peer(3)));
+        assert_eq!(slots.unchoked(), 3);
+    }
+
+    #[test]
+    fn unchoke_is_idempotent() {
+        let mut slots = UploadSlotManager::new(1);
+        assert!(slots.unchoke(peer(0)));
+        assert!(slots.unchoke(peer(0)));
+        assert_eq!(slots.unchoked(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn upload_rate_rolling_window() {
+        let mut rate = UploadRate::default();
+        assert_eq!(rate.upload_rate_bps(), 0);
+        for _ in 0..10 {
+            tokio::time::advance(Duration::from_secs(1)).await;
+            rate.record(16 * 1024);
+        }
+        assert_eq!(rate.upload_rate_bps(), 16 * 1024);
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert_eq!(rate.upload_rate_bps(), 16 * 1024 * 5 / 10);
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert_eq!(rate.upload_rate_bps(), 0);
+    }
+}
@@ -0,0 +1,185 @@
+use spate_bencode::Value;
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AnnounceError {
+    // The tracker refused the announce, with its human-readable reason.
+    Failure(String),
+    InvalidField(String),
+}
+
+impl std::fmt::Display for AnnounceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Failure(arg0) => write!(f, "Tracker failure: {}", arg0),
+            Self::InvalidField(arg0) => write!(f, "Invalid field: {}", arg0),
+        }
+    }
+}
+
+impl std::error::Error for AnnounceError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnnounceResponse {
+    // Seconds the client should wait between regular announces
+    pub interval: u32,
+    pub min_interval: Option<u32>,
+    pub tracker_id: Option<String>,
+    // Number of seeders
+    pub complete: Option<u32>,
+    // Number of leechers
+    pub incomplete: Option<u32>,
+    pub peers: Vec<SocketAddr>,
+    // Informational only; unlike `failure reason` the announce still succeeded.
+    pub warning_message: Option<String>,
+}
+
+fn get<'a>(dict: &'a BTreeMap<Value, Value>, key: &str) -> Option<&'a Value> {
+    dict.get(&Value::from(key))
+}
+
+fn as_string(value: &Value, key: &str) -> Result<String, AnnounceError> {
+    match value {
+        Value::Bytes(b) => Ok(String::from_utf8_lossy(b).into_owned()),
+        _ => Err(AnnounceError::InvalidField(format!(
+            "{} is not a string",
+            key
+        ))),
+    }
+}
+
+fn as_integer<T: TryFrom<i64>>(value: &Value, key: &str) -> Result<T, AnnounceError> {
+    match value {
+        Value::Integer(i) => T::try_from(*i)
+            .map_err(|_| AnnounceError::InvalidField(format!("{} is out of range", key))),
+        _ => Err(AnnounceError::InvalidField(format!(
+            "{} is not an integer",
+            key
+        ))),
+    }
+}
+
+fn optional<T>(
+    dict: &BTreeMap<Value, Value>,
+    key: &str,
+    f: fn(&Value, &str) -> Result<T, AnnounceError>,
+) -> Result<Option<T>, AnnounceError> {
+    get(dict, key).map(|value| f(value, key)).transpose()
+}
+
+// Peers come either in the compact form of 6 bytes per peer or as a list of dicts.
+fn peers(value: &Value) -> Result<Vec<SocketAddr>, AnnounceError> {
+    match value {
+        Value::Bytes(b) if b.len() % 6 == 0 => Ok(b
+            .chunks_exact(6)
+            .map(|c| {
+                let ip = Ipv4Addr::new(c[0], c[1], c[2], c[3]);
+                SocketAddr::new(IpAddr::V4(ip), u16::from_be_bytes([c[4], c[5]]))
+            })
+            .collect()),
+        Value::List(l) => l
+            .iter()
+            .map(|peer| {
+                let Value::Dict(peer) = peer else {
+                    return Err(AnnounceError::InvalidField(String::from(
+                        "peer is not a dict",
+                    )));
+                };
+                let ip = get(peer, "ip")
+                    .ok_or_else(|| AnnounceError::InvalidField(String::from("peer has no ip")))?;
+                let port = get(peer, "port")
+                    .ok_or_else(|| AnnounceError::InvalidField(String::from("peer has no port")))?;
+                let ip = as_string(ip, "ip")?
+                    .parse::<IpAddr>()
+                    .map_err(|e| AnnounceError::InvalidField(format!("peer ip: {}", e)))?;
+                Ok(SocketAddr::new(ip, as_integer(port, "port")?))
+            })
+            .collect(),
+        _ => Err(AnnounceError::InvalidField(String::from(
+            "peers is neither a compact string nor a list",
+        ))),
+    }
+}
+
+impl TryFrom<&Value> for AnnounceResponse {
+    type Error = AnnounceError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let Value::Dict(dict) = value else {
+            return Err(AnnounceError::InvalidField(String::from(
+                "response is not a dict",
+            )));
+        };
+        if let Some(reason) = optional(dict, "failure reason", as_string)? {
+            return Err(AnnounceError::Failure(reason));
+        }
+        Ok(Self {
+            interval: optional(dict, "interval", as_integer)?
+                .ok_or_else(|| AnnounceError::InvalidField(String::from("missing interval")))?,
+            min_interval: optional(dict, "min interval", as_integer)?,
+            tracker_id: optional(dict, "tracker id", as_string)?,
+            complete: optional(dict, "complete", as_integer)?,
+            incomplete: optional(dict, "incomplete", as_integer)?,
+            peers: get(dict, "peers")
+                .map(peers)
+                .transpose()?
+                .unwrap_or_default(),
+            warning_message: optional(dict, "warning message", as_string)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn parse(data: &[u8]) -> Result<AnnounceResponse, AnnounceError> {
+        AnnounceResponse::try_from(&Value::decode(&mut &data[..]).await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn parse_warning_message() {
+        let response = parse(
+            b"d8:completei5e10:incompletei2e8:intervali1800e5:peers6:\x0a\x00\x00\x01\x1a\xe1\
+              15:warning message26:Your client is out of datee",
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            response.warning_message.as_deref(),
+            Some("Your client is out of date")
+        );
+        assert_eq!(response.interval, 1800);
+        assert_eq!(response.complete, Some(5));
+        assert_eq!(response.peers, vec!["10.0.0.1:6881".parse().unwrap()]);
+    }
+
+    #[tokio::test]
+    async fn failure_reason_wins_over_warning() {
+        let err = parse(b"d14:failure reason6:banned15:warning message3:oope")
+            .await
+            .unwrap_err();
+        assert_eq!(err, AnnounceError::Failure(String::from("banned")));
+    }
+
+    #[tokio::test]
+    async fn parse_dict_peers() {
+        let response =
+            parse(b"d8:intervali60e5:peersld2:ip3:::14:porti6881eed2:ip8:10.0.0.24:porti51413eeee")
+                .await
+                .unwrap();
+        assert_eq!(response.warning_message, None);
+        assert_eq!(
+            response.peers,
+            vec![
+                "[::1]:6881".parse().unwrap(),
+                "10.0.0.2:51413".parse().unwrap()
+            ]
+        );
+        assert!(parse(b"d8:intervali60e5:peers5:abcdee").await.is_err());
+        assert!(parse(b"d5:peers0:e").await.is_err());
+    }
+}
@@ -0,0 +1,7 @@
+mod announce;
+mod retry;
+mod scrape;
+
+pub use announce::*;
+pub use retry::*;
+pub use scrape::*;
@@ -0,0 +1,114 @@
+use std::{future::Future, time::Duration};
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    // Total number of calls, including the first one.
+    pub max_attempts: usize,
+    pub backoff_factor: f64,
+}
+
+impl Default for RetryPolicy {
+    // Three retries after the first attempt: 15s, 30s and 60s apart.
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(15),
+            max_delay: Duration::from_secs(120),
+            max_attempts: 4,
+            backoff_factor: 2.0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn delay(&self, retry: usize) -> Duration {
+        let factor = self
+            .backoff_factor
+            .powi(retry.try_into().unwrap_or(i32::MAX));
+        // Clamp in floating point so large retry counts cannot overflow a Duration. A
+        // negative or NaN factor has no sensible backoff either, so it waits max_delay.
+        let secs = self.initial_delay.as_secs_f64() * factor;
+        Duration::try_from_secs_f64(secs).map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+
+    pub async fn retry<F, Fut, T, E>(&self, f: F) -> Result<T, E>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match f().await {
+                Ok(value) => return Ok(value),
+                Err(err) if attempt >= self.max_attempts => return Err(err),
+                Err(_) => {}
+            }
+            tokio::time::sleep(self.delay(attempt - 1)).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::time::Instant;
+
+    #[test]
+    fn delay_is_capped() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(0), Duration::from_secs(15));
+        assert_eq!(policy.delay(1), Duration::from_secs(30));
+        assert_eq!(policy.delay(2), Duration::from_secs(60));
+        assert_eq!(policy.delay(3), Duration::from_secs(120));
+        assert_eq!(policy.delay(10), Duration::from_secs(120));
+        assert_eq!(policy.delay(5000), Duration::from_secs(120));
+        assert_eq!(policy.delay(usize::MAX), Duration::from_secs(120));
+    }
+
+    #[test]
+    fn delay_with_invalid_factor() {
+        for backoff_factor in [-2.0, f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let policy = RetryPolicy {
+                backoff_factor,
+                ..Default::default()
+            };
+            assert_eq!(policy.delay(1), Duration::from_secs(120));
+            assert_eq!(policy.delay(0), Duration::from_secs(15));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_succeeds_after_failures() {
+        let calls = AtomicUsize::new(0);
+        let start = Instant::now();
+        let got = RetryPolicy::default()
+            .retry(|| async {
+                match calls.fetch_add(1, Ordering::SeqCst) {
+                    0 | 1 => Err("tracker unavailable"),
+                    n => Ok(n),
+                }
+            })
+            .await;
+        assert_eq!(got, Ok(2));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(start.elapsed(), Duration::from_secs(45));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_gives_up_after_max_attempts() {
+        let calls = AtomicUsize::new(0);
+        let start = Instant::now();
+        let got: Result<(), _> = RetryPolicy::default()
+            .retry(|| async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err("tracker unavailable")
+            })
+            .await;
+        assert_eq!(got, Err("tracker unavailable"));
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        assert_eq!(start.elapsed(), Duration::from_secs(105));
+    }
+}
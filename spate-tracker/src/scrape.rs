@@ -0,0 +1,189 @@
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    io,
+    pin::Pin,
+    time::Duration,
+};
+use tokio::time::Instant;
+
+pub type ScrapeResult<'a> =
+    Pin<Box<dyn Future<Output = Result<HashMap<[u8; 20], ScrapeStats>, io::Error>> + Send + 'a>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ScrapeStats {
+    // Number of peers with the entire file, i.e. seeders
+    pub complete: u32,
+    // Total number of times the tracker has registered a completion
+    pub downloaded: u32,
+    // Number of non-seeder peers, aka "leechers"
+    pub incomplete: u32,
+}
+
+pub trait Scraper {
+    fn url(&self) -> &str;
+
+    fn scrape<'a>(&'a self, info_hashes: &'a [[u8; 20]]) -> ScrapeResult<'a>;
+}
+
+pub struct TrackerScrapeCache {
+    pub max_age: Duration,
+    // Keyed by tracker url first so lookups can borrow the url.
+    entries: HashMap<String, HashMap<[u8; 20], (ScrapeStats, Instant)>>,
+}
+
+impl TrackerScrapeCache {
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            max_age,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub async fn get_or_scrape(
+        &mut self,
+        tracker: &dyn Scraper,
+        info_hashes: &[[u8; 20]],
+    ) -> Result<HashMap<[u8; 20], ScrapeStats>, io::Error> {
+        let now = Instant::now();
+        let mut stats = HashMap::with_capacity(info_hashes.len());
+        let mut missing = Vec::new();
+        let mut seen = HashSet::with_capacity(info_hashes.len());
+        let cached = self.entries.get(tracker.url());
+        for info_hash in info_hashes {
+            match cached.and_then(|entries| entries.get(info_hash)) {
+                Some((entry, scraped)) if now.duration_since(*scraped) < self.max_age => {
+                    stats.insert(*info_hash, *entry);
+                }
+                _ if seen.insert(*info_hash) => missing.push(*info_hash),
+                _ => {}
+            }
+        }
+        // Everything that is stale goes to the tracker in a single request.
+        if !missing.is_empty() {
+            let scraped = tracker.scrape(&missing).await?;
+            self.prune(now);
+            let entries = self.entries.entry(tracker.url().to_string()).or_default();
+            for (info_hash, entry) in scraped {
+                entries.insert(info_hash, (entry, now));
+                stats.insert(info_hash, entry);
+            }
+        }
+        Ok(stats)
+    }
+
+    // Pruning happens whenever new entries are inserted, so the cache doesn't keep
+    // torrents or trackers that are no longer asked for.
+    fn prune(&mut self, now: Instant) {
+        let max_age = self.max_age;
+        self.entries.retain(|_, entries| {
+            entries.retain(|_, (_, scraped)| now.duration_since(*scraped) < max_age);
+            !entries.is_empty()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    struct MockScraper {
+        url: String,
+        requests: Mutex<Vec<Vec<[u8; 20]>>>,
+    }
+
+    impl MockScraper {
+        fn new(url: &str) -> Self {
+            Self {
+                url: url.to_string(),
+                requests: Mutex::new(vec![]),
+            }
+        }
+
+        fn requests(&self) -> Vec<Vec<[u8; 20]>> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    impl Scraper for MockScraper {
+        fn url(&self) -> &str {
+            &self.url
+        }
+
+        fn scrape<'a>(&'a self, info_hashes: &'a [[u8; 20]]) -> ScrapeResult<'a> {
+            Box::pin(async move {
+                self.requests.lock().unwrap().push(info_hashes.to_vec());
+                Ok(info_hashes
+                    .iter()
+                    .map(|h| {
+                        let stats = ScrapeStats {
+                            complete: h[0] as u32,
+                            ..Default::default()
+                        };
+                        (*h, stats)
+                    })
+                    .collect())
+            })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn scrapes_once_per_max_age() {
+        let tracker = MockScraper::new("udp://tracker:80");
+        let mut cache = TrackerScrapeCache::new(Duration::from_secs(60));
+        for _ in 0..10 {
+            let stats = cache.get_or_scrape(&tracker, &[[1; 20]]).await.unwrap();
+            assert_eq!(stats[&[1; 20]].complete, 1);
+            tokio::time::advance(Duration::from_secs(5)).await;
+        }
+        assert_eq!(tracker.requests().len(), 1);
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        cache.get_or_scrape(&tracker, &[[1; 20]]).await.unwrap();
+        assert_eq!(tracker.requests().len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn only_scrapes_uncached_hashes() {
+        let tracker = MockScraper::new("udp://tracker:80");
+        let mut cache = TrackerScrapeCache::new(Duration::from_secs(60));
+        cache.get_or_scrape(&tracker, &[[1; 20]]).await.unwrap();
+        let stats = cache
+            .get_or_scrape(&tracker, &[[1; 20], [2; 20], [3; 20], [2; 20]])
+            .await
+            .unwrap();
+        assert_eq!(stats.len(), 3);
+        assert_eq!(
+            tracker.requests(),
+            vec![vec![[1; 20]], vec![[2; 20], [3; 20]]]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn prunes_expired_entries() {
+        let first = MockScraper::new("udp://first:80");
+        let second = MockScraper::new("udp://second:80");
+        let mut cache = TrackerScrapeCache::new(Duration::from_secs(60));
+        cache
+            .get_or_scrape(&first, &[[1; 20], [2; 20]])
+            .await
+            .unwrap();
+        tokio::time::advance(Duration::from_secs(60)).await;
+        cache.get_or_scrape(&second, &[[3; 20]]).await.unwrap();
+        assert_eq!(cache.entries.len(), 1);
+        assert_eq!(cache.entries["udp://second:80"].len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn caches_per_tracker() {
+        let first = MockScraper::new("udp://first:80");
+        let second = MockScraper::new("udp://second:80");
+        let mut cache = TrackerScrapeCache::new(Duration::from_secs(60));
+        cache.get_or_scrape(&first, &[[1; 20]]).await.unwrap();
+        cache.get_or_scrape(&second, &[[1; 20]]).await.unwrap();
+        cache.get_or_scrape(&first, &[[1; 20]]).await.unwrap();
+        assert_eq!(first.requests().len(), 1);
+        assert_eq!(second.requests().len(), 1);
+    }
+}
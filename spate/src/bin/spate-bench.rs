@@ -0,0 +1,114 @@
+use spate_bencode::Value;
+use spate_metainfo::verify_piece;
+use spate_peer::Handshake;
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+const TORRENT: &[u8] = include_bytes!("../../resources/ubuntu-23.10.1-desktop-amd64.iso.torrent");
+const MIN_DURATION: Duration = Duration::from_secs(1);
+const PIECE_LENGTH: usize = 256 * 1024;
+const MIB: f64 = 1024.0 * 1024.0;
+
+// Runs `f` until at least MIN_DURATION has passed, returning the iteration count and the
+// time they took.
+async fn run<F, Fut>(mut f: F) -> (u64, Duration)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let start = Instant::now();
+    let mut iterations = 0;
+    while start.elapsed() < MIN_DURATION {
+        f().await;
+        iterations += 1;
+    }
+    (iterations, start.elapsed())
+}
+
+async fn bench_decode() -> String {
+    let (n, elapsed) = run(|| async {
+        Value::decode(&mut &TORRENT[..]).await.unwrap();
+    })
+    .await;
+    let rate = (n as usize * TORRENT.len()) as f64 / elapsed.as_secs_f64();
+    format!("{:.2} MiB/s", rate / MIB)
+}
+
+async fn bench_sha1() -> String {
+    let piece = vec![0xa5; PIECE_LENGTH];
+    let (n, elapsed) = run(|| async {
+        // A mismatch still hashes the whole piece, which is all that is measured here.
+        let _ = verify_piece(&piece, &[0; 20], 0);
+    })
+    .await;
+    let rate = (n as usize * PIECE_LENGTH) as f64 / elapsed.as_secs_f64();
+    format!("{:.2} MiB/s", rate / MIB)
+}
+
+async fn bench_announce() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut response = b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n".to_vec();
+        response.extend_from_slice(b"d8:intervali1800e5:peers0:e");
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            let _ = stream.read(&mut buf).await;
+            let _ = stream.write_all(&response).await;
+        }
+    });
+
+    let request = b"GET /announce?info_hash=%00&port=6881 HTTP/1.1\r\nHost: localhost\r\n\r\n";
+    let (n, elapsed) = run(|| async {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request).await.unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+    })
+    .await;
+    format!("{:.1} µs", elapsed.as_micros() as f64 / n as f64)
+}
+
+async fn bench_handshake() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let ours = Handshake::new([1; 20], *b"-SP0001-benchmarking");
+    let theirs = Handshake::new([1; 20], *b"-qB4530-benchmarking");
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            if Handshake::read(&mut stream).await.is_ok() {
+                let _ = theirs.write(&mut stream).await;
+            }
+        }
+    });
+
+    let (n, elapsed) = run(|| async {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        ours.write(&mut stream).await.unwrap();
+        Handshake::read(&mut stream).await.unwrap();
+    })
+    .await;
+    format!("{:.0} handshakes/s", n as f64 / elapsed.as_secs_f64())
+}
+
+#[tokio::main]
+async fn main() {
+    let results = [
+        ("bencode decode", bench_decode().await),
+        ("piece SHA-1", bench_sha1().await),
+        ("tracker announce", bench_announce().await),
+        ("peer handshake", bench_handshake().await),
+    ];
+    println!("{:<20} result", "benchmark");
+    for (name, result) in results {
+        println!("{:<20} {}", name, result);
+    }
+}